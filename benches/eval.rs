@@ -0,0 +1,79 @@
+// Measures the time it takes to evaluate programs that make many calls,
+// including calls through a parameter, whose callee isn't known until the
+// program runs. Run with `cargo bench --bench eval`.
+
+extern crate jsonpl;
+
+use jsonpl::vm;
+use std::time::{Duration, Instant};
+
+const CALLS: usize = 100_000;
+const RUNS: usize = 3;
+
+const PROGRAMS: [(&str, &str); 3] = [
+    (
+        "Direct calls",
+        "
+let:
+  inc:
+    lambda: n
+    +in: { +: [{ $: n }, 1] }
++in:
+  map: [{ $: inc }, { $: numbers }]
+",
+    ),
+    (
+        "Calls through a parameter",
+        "
+let:
+  apply:
+    lambda: [g, x]
+    +in: { g: { $: x } }
+  inc:
+    lambda: n
+    +in: { +: [{ $: n }, 1] }
++in:
+  map:
+    - lambda: i
+      +in: { apply: [{ $: inc }, { $: i }] }
+    - { $: numbers }
+",
+    ),
+    (
+        "Named calls through a parameter",
+        "
+let:
+  apply:
+    lambda: [g, x]
+    +in: { g: { lhs: { $: x }, rhs: 1 } }
+  add:
+    lambda: { lhs, rhs }
+    +in: { +: [{ $: lhs }, { $: rhs }] }
++in:
+  map:
+    - lambda: i
+      +in: { apply: [{ $: add }, { $: i }] }
+    - { $: numbers }
+",
+    ),
+];
+
+fn main() {
+    let numbers: Vec<usize> = (0..CALLS).collect();
+    let mut engine = vm::Engine::new();
+    engine.bind_value("numbers", vm::to_value(&numbers).unwrap());
+    let context = engine.context("eval.yapl").unwrap();
+    for (name, source) in PROGRAMS {
+        let program = vm::parse(source).unwrap();
+        // The first run also pays for the allocator growing the heap, so the
+        // fastest of several runs is reported.
+        let mut time = Duration::MAX;
+        for _ in 0..RUNS {
+            let start = Instant::now();
+            let result = context.eval(&program).unwrap();
+            time = time.min(start.elapsed());
+            assert_eq!(vm::Value::as_array(&result).unwrap().len(), CALLS);
+        }
+        println!("{}: {:?}", name, time);
+    }
+}
@@ -0,0 +1,92 @@
+// Measures the memory retained by a parsed document and the time it takes to
+// parse and serialize it. Run with `cargo bench --bench roundtrip`.
+
+extern crate jsonpl;
+
+use jsonpl::vm;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        ALLOCATIONS.fetch_sub(1, Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const RECORDS: usize = 50_000;
+const RUNS: usize = 3;
+
+// Builds a document shaped like a typical dataset: many records with the same
+// keys, short string fields, and numbers.
+fn document() -> String {
+    let records: Vec<serde_json::Value> = (0..RECORDS)
+        .map(|i| {
+            serde_json::json!({
+                "id": i,
+                "name": format!("user{}", i),
+                "email": format!("user{}@example.com", i),
+                "active": i % 2 == 0,
+                "score": i as f64 / 7.0,
+                "tags": ["red", "green", "blue"],
+                "address": {
+                    "city": "Springfield",
+                    "postal_code": format!("{:05}", i % 100_000),
+                    "country_code": "US",
+                },
+                "description_of_the_account": "A longer string that will not fit inline anywhere",
+            })
+        })
+        .collect();
+    serde_json::to_string(&records).unwrap()
+}
+
+fn main() {
+    let json = document();
+    println!("Document: {} bytes, {} records", json.len(), RECORDS);
+    println!(
+        "size_of::<Value>(): {} bytes",
+        std::mem::size_of::<vm::Value>()
+    );
+
+    // The first run also pays for the allocator growing the heap, so the
+    // fastest of several runs is reported.
+    let mut parse_time = Duration::MAX;
+    let mut serialize_time = Duration::MAX;
+    for _ in 0..RUNS {
+        let before = ALLOCATED.load(Ordering::Relaxed);
+        let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+        let start = Instant::now();
+        let value = vm::parse(&json).unwrap();
+        parse_time = parse_time.min(start.elapsed());
+        let retained = ALLOCATED.load(Ordering::Relaxed) - before;
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations_before;
+
+        let start = Instant::now();
+        let output = vm::serialize(&value).unwrap();
+        serialize_time = serialize_time.min(start.elapsed());
+        assert_eq!(output.len(), json.len());
+        println!(
+            "Retained after parse: {} bytes in {} allocations",
+            retained, allocations
+        );
+    }
+    println!("Parse: {:?}", parse_time);
+    println!("Serialize: {:?}", serialize_time);
+}
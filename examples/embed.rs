@@ -0,0 +1,78 @@
+extern crate jsonpl;
+
+use jsonpl::vm;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+const PROGRAM: &str = "
+import: { ids: { names: [next_id] } }
++in: [{ $: settings }, { twice: { next_id: null } }, { next_id: null }, { repeat: [ab, 3] }, { is_match: [{ regex: ^a+b$ }, aab] }]
+";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Settings {
+    greeting: String,
+    verbose: bool,
+}
+
+// Runs a program with natives that share state with the host.
+fn main() -> Result<(), vm::Error> {
+    let counter = Arc::new(AtomicU64::new(0));
+    let mut engine = vm::Engine::new();
+    let settings = Settings {
+        greeting: "hello".to_string(),
+        verbose: false,
+    };
+    engine.bind_value("settings", vm::to_value(&settings)?);
+    // A special form receives its arguments unevaluated.
+    engine.bind_special_form("twice", |env, _object, args| {
+        vm::eval(env, args)?;
+        vm::eval(env, args)
+    });
+    // A typed native has its arguments converted to its parameter types, and
+    // trailing `Option` parameters may be left out.
+    engine.bind_typed_function("repeat", |text: vm::Str, times: Option<usize>| {
+        text.repeat(times.unwrap_or(2))
+    });
+    // Host resources are passed to programs as opaque handles, which natives
+    // downcast to get the resource back.
+    engine.bind_typed_function(
+        "regex",
+        |pattern: vm::Str| -> Result<vm::Opaque, vm::Error> {
+            let regex = regex::Regex::new(&pattern)
+                .map_err(|err| vm::Error::InvalidPattern(err.to_string()))?;
+            Ok(vm::Opaque::new("regex", Arc::new(regex)))
+        },
+    );
+    engine.bind_typed_function("is_match", |regex: Arc<vm::Opaque>, text: vm::Str| {
+        Ok(regex.downcast_ref::<regex::Regex>()?.is_match(&text))
+    });
+    let mut ids = vm::NativeModule::new();
+    let next = counter.clone();
+    ids.bind_function("next_id", move |_env, _args| {
+        Ok(vm::Value::Number(
+            next.fetch_add(1, Ordering::SeqCst).into(),
+        ))
+    });
+    engine.register_module("ids", ids);
+
+    let context = engine.context("embed.yapl")?;
+    let program = vm::parse(PROGRAM)?;
+    for problem in context.check(&program) {
+        println!("{}", problem);
+    }
+    let result = context.eval(&program)?;
+    println!("{}", vm::serialize(&result)?);
+    let (settings, first, second, repeated, matched): (Settings, u64, u64, String, bool) =
+        vm::from_value(&result)?;
+    println!(
+        "{:?} got ids {} and {}, {}, {}",
+        settings, first, second, repeated, matched
+    );
+    println!(
+        "next_id was called {} times",
+        counter.load(Ordering::SeqCst)
+    );
+    Ok(())
+}
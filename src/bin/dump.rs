@@ -1,8 +1,6 @@
 extern crate jsonpl;
 
 use anyhow::Result;
-use serde_json;
-use serde_yaml;
 use std::env;
 use std::fs;
 use std::process::exit;
@@ -3,19 +3,32 @@ extern crate jsonpl;
 use anyhow::Result;
 use jsonpl::vm;
 use std::env;
-use std::fs;
+use std::path::PathBuf;
 use std::process::exit;
 
+fn usage(program: &str) -> ! {
+    println!("Usage: {} [-I <dir>]... [--prelude] <file>", program);
+    exit(1);
+}
+
 fn main() -> Result<()> {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: {} <file>", args[0]);
-        exit(1);
+    let mut engine = vm::Engine::new();
+    let mut path = None;
+    let mut rest = args.iter().skip(1);
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "-I" => engine.search_path.push(PathBuf::from(
+                rest.next().unwrap_or_else(|| usage(&args[0])),
+            )),
+            "--prelude" => engine.prelude = true,
+            _ if path.is_none() => path = Some(arg),
+            _ => usage(&args[0]),
+        }
     }
-    let path = &args[1];
-    let input = fs::read_to_string(path)?;
-    let env = vm::Env::builtin(path.to_string());
-    match vm::parse(&input).and_then(|value| vm::eval(&env, &value)) {
+    let path = path.unwrap_or_else(|| usage(&args[0]));
+    engine.search_path.extend(vm::search_path_from_env());
+    match engine.run_file(path) {
         Ok(_) => Ok(()),
         Err(err) => {
             eprintln!("Error: {:?}", err);
@@ -1,22 +1,26 @@
 extern crate jsonpl;
 
 use jsonpl::vm;
-use rustyline;
-use std::sync::Arc;
+use std::env;
 
-fn read_eval_print(env: &Arc<vm::Env>, input: &str) -> Result<String, vm::Error> {
-    let value = vm::parse(input)?;
-    let value = vm::eval(env, &value)?;
-    vm::serialize(&value)
+fn read_eval_print(context: &vm::Context, input: &str) -> Result<String, vm::Error> {
+    vm::serialize(&context.run(input)?)
 }
 
 fn main() -> rustyline::Result<()> {
     let mut rl = rustyline::Editor::<()>::new()?;
-    let env = vm::Env::builtin(".".to_string());
+    // Imports are resolved as if the REPL were a file in the current directory.
+    let file = env::current_dir()?.join("repl");
+    let mut engine = vm::Engine::new();
+    engine.search_path = vm::search_path_from_env();
+    engine.prelude = env::args().any(|arg| arg == "--prelude");
+    let context = engine
+        .context(&file.display().to_string())
+        .expect("std/prelude loads");
     loop {
         let readline = rl.readline(">> ");
         match readline {
-            Ok(line) => match read_eval_print(&env, &line) {
+            Ok(line) => match read_eval_print(&context, &line) {
                 Ok(json) => println!("{}", json),
                 Err(err) => println!("Error: {:?}", err),
             },
@@ -6,25 +6,129 @@ use std::env;
 use std::fs;
 use std::io;
 use std::io::Read;
+use std::path::PathBuf;
 use std::process::exit;
+use std::str::FromStr;
+use std::time::Duration;
+
+fn usage(program: &str) -> ! {
+    println!(
+        "Usage: {} [--input-schema <schema>] [--output-schema <schema>] [--sort-keys] [-I <dir>]... [--prelude] [--max-steps <n>] [--max-call-depth <n>] [--max-collection-size <n>] [--timeout <ms>] <file>",
+        program
+    );
+    exit(1);
+}
+
+fn parse_limit<T: FromStr>(program: &str, value: Option<&String>) -> Option<T> {
+    let value = value.unwrap_or_else(|| usage(program));
+    Some(value.parse().unwrap_or_else(|_| usage(program)))
+}
+
+fn load_schema(path: &Option<String>) -> Result<Option<vm::Value>> {
+    match path {
+        Some(path) => {
+            let schema = fs::read_to_string(path)?;
+            match vm::parse(&schema) {
+                Ok(schema) => Ok(Some(schema)),
+                Err(err) => {
+                    eprintln!("Error: {:?} in schema {}", err, path);
+                    exit(1);
+                }
+            }
+        }
+        None => Ok(None),
+    }
+}
+
+fn check_schema(schema: &Option<vm::Value>, value: &vm::Value, what: &str) {
+    let schema = match schema {
+        Some(schema) => schema,
+        None => return,
+    };
+    match vm::validate(schema, value) {
+        Ok(errors) if errors.is_empty() => {}
+        Ok(errors) => {
+            for error in errors.iter() {
+                eprintln!(
+                    "Invalid {} at {:?} ({}): {}",
+                    what, error.instance_path, error.schema_path, error.message
+                );
+            }
+            exit(1);
+        }
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            exit(1);
+        }
+    }
+}
 
 fn main() -> Result<()> {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: {} <file>", args[0]);
-        exit(1);
+    let mut input_schema = None;
+    let mut output_schema = None;
+    let mut sort_keys = false;
+    let mut engine = vm::Engine::new();
+    let mut limits = vm::Limits::default();
+    let mut path = None;
+    let mut rest = args.iter().skip(1);
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--input-schema" => input_schema = Some(rest.next().unwrap_or_else(|| usage(&args[0]))),
+            "--output-schema" => {
+                output_schema = Some(rest.next().unwrap_or_else(|| usage(&args[0])))
+            }
+            "--sort-keys" => sort_keys = true,
+            "--prelude" => engine.prelude = true,
+            "-I" => engine.search_path.push(PathBuf::from(
+                rest.next().unwrap_or_else(|| usage(&args[0])),
+            )),
+            "--max-steps" => limits.max_steps = parse_limit(&args[0], rest.next()),
+            "--max-call-depth" => limits.max_call_depth = parse_limit(&args[0], rest.next()),
+            "--max-collection-size" => {
+                limits.max_collection_size = parse_limit(&args[0], rest.next())
+            }
+            "--timeout" => {
+                let millis = parse_limit(&args[0], rest.next());
+                limits.timeout = millis.map(Duration::from_millis);
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => usage(&args[0]),
+        }
     }
-    let path = &args[1];
+    let path = path.unwrap_or_else(|| usage(&args[0]));
+    let input_schema = load_schema(&input_schema.cloned())?;
+    let output_schema = load_schema(&output_schema.cloned())?;
     let input = fs::read_to_string(path)?;
-    let env = vm::Env::builtin(path.to_string());
-    match vm::parse(&input).and_then(|value| vm::eval(&env, &value)) {
+    engine.search_path.extend(vm::search_path_from_env());
+    engine.set_limits(limits);
+    let context = match engine.context(path) {
+        Ok(context) => context,
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            exit(1);
+        }
+    };
+    match context.run(&input) {
         Ok(transform) => {
             let mut input_data = String::new();
             io::stdin().read_to_string(&mut input_data)?;
             let input = vm::parse(&input_data).unwrap();
-            let function = vm::Value::as_function(&transform).unwrap();
-            let output_data = function.call(&env, &input).unwrap();
-            println!("{}", vm::serialize(&output_data).unwrap());
+            check_schema(&input_schema, &input, "input");
+            let output_data = match context.call(&transform, &input) {
+                Ok(output_data) => output_data,
+                Err(err) => {
+                    eprintln!("Error: {:?}", err);
+                    exit(1);
+                }
+            };
+            check_schema(&output_schema, &output_data, "output");
+            let output = if sort_keys {
+                vm::serialize_sorted(&output_data)
+            } else {
+                vm::serialize(&output_data)
+            };
+            println!("{}", output.unwrap());
             Ok(())
         }
         Err(err) => {
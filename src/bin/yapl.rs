@@ -0,0 +1,79 @@
+extern crate jsonpl;
+
+use anyhow::Result;
+use jsonpl::vm;
+use std::env;
+use std::path::PathBuf;
+use std::process::exit;
+
+fn usage(program: &str) -> ! {
+    println!(
+        "Usage: {} <command> [-I <dir>]... [--prelude] <file>...",
+        program
+    );
+    println!();
+    println!("Commands:");
+    println!("    run     Evaluate a program");
+    println!("    check   Report problems in programs, or modules of the standard library");
+    println!("            such as std/list, without running them");
+    println!();
+    println!("Options:");
+    println!("    -I <dir>   Search <dir> for imported modules, before YAPL_PATH");
+    println!("    --prelude  Bind the exports of std/prelude before the program runs");
+    exit(1);
+}
+
+fn run(engine: &vm::Engine, path: &str) -> Result<()> {
+    if let Err(err) = engine.run_file(path) {
+        eprintln!("Error: {:?}", err);
+        exit(1);
+    }
+    Ok(())
+}
+
+fn check(engine: &vm::Engine, paths: &[&String]) -> Result<()> {
+    let mut failed = false;
+    for path in paths.iter() {
+        match engine.check_file(path) {
+            Ok(problems) => {
+                for problem in problems.iter() {
+                    println!("{}", problem);
+                }
+                failed |= !problems.is_empty();
+            }
+            Err(err) => {
+                eprintln!("{}: Error: {:?}", path, err);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        exit(1);
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        usage(&args[0]);
+    }
+    let mut engine = vm::Engine::new();
+    let mut paths = Vec::new();
+    let mut rest = args.iter().skip(2);
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "-I" => engine.search_path.push(PathBuf::from(
+                rest.next().unwrap_or_else(|| usage(&args[0])),
+            )),
+            "--prelude" => engine.prelude = true,
+            _ => paths.push(arg),
+        }
+    }
+    engine.search_path.extend(vm::search_path_from_env());
+    match (args[1].as_str(), paths.as_slice()) {
+        ("run", [path]) => run(&engine, path),
+        ("check", [_, ..]) => check(&engine, &paths),
+        _ => usage(&args[0]),
+    }
+}
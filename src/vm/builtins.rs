@@ -1,40 +1,102 @@
 use super::*;
-use std::fs;
 use std::path::Path;
 
-pub fn println(_env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
-    println!("{}", Value::as_string(args)?);
-    Ok(Value::null())
+const PATTERN_KEY: &str = "+pattern";
+const VALUE_KEY: &str = "+value";
+
+pub fn println(line: Str) {
+    println!("{}", line);
 }
 
-pub fn print(_env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
-    print!("{}", Value::as_string(args)?);
-    Ok(Value::null())
+pub fn print(text: Str) {
+    print!("{}", text);
 }
 
-pub fn deserialize(_env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
-    let string = Value::as_string(&args)?;
-    Ok(parse(&string)?)
+pub fn deserialize(string: Str) -> Result<Value, Error> {
+    parse(&string)
 }
 
-pub fn serialize(_env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
-    Ok(Value::String(Arc::new(super::serialize(args)?)))
+pub fn serialize(value: Value) -> Result<String, Error> {
+    super::serialize(&value)
 }
 
-fn get_formals(args: &Value) -> Result<Formals, Error> {
-    match args {
-        Value::String(name) => Ok(Formals::Singleton(name.clone())),
-        Value::Array(names) => {
-            let strings: Result<Vec<&Arc<String>>, Error> =
-                names.iter().map(|name| Value::as_string(name)).collect();
-            Ok(Formals::Positional(
-                strings?.iter().map(|name| Arc::clone(name)).collect(),
-            ))
+pub fn html(value: Value) -> Result<String, Error> {
+    super::html::render(&value)
+}
+
+pub fn validate(schema: Value, instance: Value) -> Result<Vec<Value>, Error> {
+    Ok(super::validate(&schema, &instance)?
+        .iter()
+        .map(ValidationError::to_value)
+        .collect())
+}
+
+pub fn query(value: Value, query: Value) -> Result<Vec<Value>, Error> {
+    Ok(Query::parse(&query)?.run(&value))
+}
+
+// Reads the options that may follow the arguments of the tree walking
+// functions. `{ path: true }` passes each node's path along with the node.
+fn get_with_path(args: &[Value], index: usize) -> Result<bool, Error> {
+    let options = match args.get(index) {
+        Some(options) => Value::as_object(options)?,
+        None => return Ok(false),
+    };
+    let mut with_path = false;
+    for (key, value) in options.iter() {
+        match key.as_str() {
+            "path" => with_path = Value::as_bool(value)?,
+            _ => return Err(Error::InvalidOperation(format!("Unknown option: {}", key))),
         }
-        Value::Object(names) => Ok(Formals::Named(
-            names.keys().map(|name| Arc::new(name.clone())).collect(),
-        )),
+    }
+    Ok(with_path)
+}
+
+fn get_walker<'a>(
+    env: &'a Arc<Env>,
+    args: &'a [Value],
+    index: usize,
+    with_path: bool,
+) -> Result<Walker<'a>, Error> {
+    Ok(Walker {
+        env,
+        function: Value::as_function(get_index(args, index)?)?,
+        with_path,
+    })
+}
+
+pub fn walk(env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
+    let args = Value::as_array(args)?;
+    let with_path = get_with_path(args, 3)?;
+    let inner = get_walker(env, args, 0, with_path)?;
+    let outer = get_walker(env, args, 1, with_path)?;
+    super::walk::walk(&inner, &outer, get_index(args, 2)?)
+}
+
+pub fn prewalk(env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
+    let args = Value::as_array(args)?;
+    let walker = get_walker(env, args, 0, get_with_path(args, 2)?)?;
+    super::walk::prewalk(&walker, get_index(args, 1)?)
+}
+
+pub fn postwalk(env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
+    let args = Value::as_array(args)?;
+    let walker = get_walker(env, args, 0, get_with_path(args, 2)?)?;
+    super::walk::postwalk(&walker, get_index(args, 1)?)
+}
 
+pub fn replace_map(env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
+    let args = Value::as_array(args)?;
+    let query = Query::parse(get_index(args, 0)?)?;
+    let walker = get_walker(env, args, 1, get_with_path(args, 3)?)?;
+    super::walk::replace_map(&query, &walker, get_index(args, 2)?)
+}
+
+pub fn get_formals(args: &Value) -> Result<Formals, Error> {
+    match args {
+        Value::String(name) => Ok(Formals::Singleton(Arc::new(name.to_string()))),
+        Value::Array(_) => Ok(Formals::Positional(Pattern::parse_binding(args)?)),
+        Value::Object(object) => Ok(Formals::Named(Pattern::parse_named_formals(object)?)),
         _ => Err(Error::invalid_type(
             "Formal parameters (string, array, or object)",
             args,
@@ -42,85 +104,191 @@ fn get_formals(args: &Value) -> Result<Formals, Error> {
     }
 }
 
-pub fn lambda(env: &Arc<Env>, object: &Object, args: &Value) -> Result<Value, Error> {
-    Ok(Value::Function(Arc::new(Function {
-        body: FunctionBody::Lambda(Lambda {
-            env: env.clone(),
-            formals: get_formals(args)?,
-            body: get_key(object, "+in")?.clone(),
-        }),
-    })))
+// A `let` value such as `{ +pattern: { user: { name, email } }, +value: ... }`
+// destructures `+value` with a binding pattern, besides binding it to its key
+// as usual. Returns the pattern and the unevaluated value.
+pub fn get_binding_pattern(value: &Value) -> Result<Option<(Pattern, &Value)>, Error> {
+    let object = match value {
+        Value::Object(object) if object.contains_key(PATTERN_KEY) => object,
+        _ => return Ok(None),
+    };
+    for key in object.keys() {
+        if key != PATTERN_KEY && key != VALUE_KEY {
+            return Err(Error::InvalidPattern(format!("Unknown key: {}", key)));
+        }
+    }
+    let pattern = Pattern::parse_binding(&object[PATTERN_KEY])?;
+    Ok(Some((pattern, get_key(object, VALUE_KEY)?)))
 }
 
-pub fn lookup(env: &Arc<Env>, _object: &Object, args: &Value) -> Result<Value, Error> {
-    // TODO: Support pathing operators.
-    Ok(env.lookup(Value::as_string(args)?)?.clone())
+// Parses the optional `+types` annotations of a lambda, which map parameter
+// names to types.
+pub fn get_parameter_types(
+    object: &Object,
+    formals: &Formals,
+) -> Result<Vec<(Arc<String>, Type)>, Error> {
+    let annotations = match object.get("+types") {
+        Some(annotations) => Value::as_object(annotations)?,
+        None => return Ok(Vec::new()),
+    };
+    let names = formals.names();
+    annotations
+        .iter()
+        .map(|(name, annotation)| {
+            let name = names
+                .iter()
+                .find(|formal| formal.as_str() == name.as_str())
+                .ok_or_else(|| Error::UndefinedSymbol(name.to_string()))?;
+            Ok((name.clone(), Type::parse(annotation)?))
+        })
+        .collect()
 }
 
-pub fn quote(_env: &Arc<Env>, _object: &Object, args: &Value) -> Result<Value, Error> {
-    Ok(args.clone())
+// How one entry of an `import` mapping binds what it imports: a string binds
+// it to that name, null binds each of its keys, and an object gives the
+// binding under `as`, an explicit `format`, and the `names` to import, either
+// as a list or as an object renaming each name.
+pub struct ImportSpec<'a> {
+    pub binding: Option<&'a Str>,
+    pub format: Option<Format>,
+    pub names: Option<Vec<(Str, Str)>>,
 }
 
-// TODO: In this version of let, the values being bound to variables cannot see
-// themselves or other variables being bound. Eventually, we'll want letrec,
-// which will allow variables to see other variables, but involves a mutation
-// somewhere.
-pub fn nonrecursive_let(env: &Arc<Env>, object: &Object, args: &Value) -> Result<Value, Error> {
-    let bindings = Value::as_object(args)?;
-    let variables = bindings
-        .iter()
-        .map(|(name, value)| {
-            let value = eval(env, value)?;
-            Ok((name.clone(), value))
-        })
-        .collect::<Result<ObjectMap, Error>>()?;
-    let child_env = Env::new(variables, Some(env.clone()));
-    eval(&child_env, get_key(object, "+in")?)
+pub fn get_import_spec(value: &Value) -> Result<ImportSpec<'_>, Error> {
+    let mut spec = ImportSpec {
+        binding: None,
+        format: None,
+        names: None,
+    };
+    let options = match value {
+        Value::String(name) => {
+            spec.binding = Some(name);
+            return Ok(spec);
+        }
+        Value::Null => return Ok(spec),
+        Value::Object(options) => options,
+        _ => {
+            return Err(Error::invalid_type(
+                "import mapping (string, null, or object)",
+                value,
+            ))
+        }
+    };
+    for (key, value) in options.iter() {
+        match key.as_str() {
+            "as" => {
+                spec.binding = match value {
+                    Value::Null => None,
+                    value => Some(Value::as_string(value)?),
+                }
+            }
+            "format" => {
+                let name = Value::as_str(value)?;
+                spec.format = Some(Format::named(name).ok_or_else(|| {
+                    Error::InvalidOperation(format!("Unknown import format: {}", name))
+                })?);
+            }
+            "names" => spec.names = Some(get_import_names(value)?),
+            _ => {
+                return Err(Error::InvalidOperation(format!(
+                    "Unknown import option: {}",
+                    key
+                )))
+            }
+        }
+    }
+    Ok(spec)
 }
 
-pub fn import(env: &Arc<Env>, object: &Object, args: &Value) -> Result<Value, Error> {
-    let mut variables = ObjectMap::new();
+// Returns the pairs of exported and local names listed by `names`.
+fn get_import_names(names: &Value) -> Result<Vec<(Str, Str)>, Error> {
+    match names {
+        Value::Array(names) => names
+            .iter()
+            .map(|name| {
+                let name = Value::as_string(name)?;
+                Ok((name.clone(), name.clone()))
+            })
+            .collect(),
+        Value::Object(names) => names
+            .iter()
+            .map(|(name, local)| Ok((name.clone(), Value::as_string(local)?.clone())))
+            .collect(),
+        _ => Err(Error::invalid_type("list or object of names", names)),
+    }
+}
+
+// Loads the modules named by the `import` mapping `args` and returns the
+// variables they bind. Two modules binding the same name is an error.
+pub fn load_imports(env: &Arc<Env>, args: &Value) -> Result<Variables, Error> {
+    let mut variables = Variables::new();
     let modules = Value::as_object(args)?;
-    let file_path = Path::new(Value::as_str(env.lookup(FILE_SYMBOL)?)?);
-    let file_dir = file_path.parent().unwrap();
+    let file_path = Value::as_str(env.lookup(FILE_SYMBOL)?)?;
     for (name, value) in modules.iter() {
-        let path_name = format!("{}.yapl", name);
-        let path = file_dir.join(path_name);
-        let program = fs::read_to_string(&path).map_err(|_| Error::IO)?;
-        let parsed_program = parse(&program)?;
-        let root_env = Env::builtin(path.display().to_string());
-        let exports = eval(&root_env, &parsed_program)?;
-        match value {
-            Value::String(name) => {
-                variables.insert(name.to_string(), exports);
+        let spec = get_import_spec(value)?;
+        let modules = env.modules();
+        let location = modules.resolve(file_path, name)?;
+        let format = spec.format.unwrap_or_else(|| location.default_format());
+        let mut exports = modules.load(Path::new(file_path), &location, format)?;
+        if let Some(names) = &spec.names {
+            let all = Value::as_object(&exports)?;
+            let mut selected = ObjectMap::with_capacity(names.len());
+            for (export, local) in names.iter() {
+                let value = all
+                    .get(export)
+                    .ok_or_else(|| Error::MissingExport(name.to_string(), export.to_string()))?;
+                selected.insert(local.clone(), value.clone());
             }
-            Value::Null => {
+            exports = Value::Object(Arc::new(selected));
+        }
+        let mut bind = |name: &str, value: Value| match variables.entry(name.to_string()) {
+            indexmap::map::Entry::Occupied(_) => Err(Error::NameCollision(name.to_string())),
+            indexmap::map::Entry::Vacant(entry) => {
+                entry.insert(value);
+                Ok(())
+            }
+        };
+        match spec.binding {
+            Some(name) => bind(name, exports)?,
+            None => {
                 for (name, value) in Value::as_object(&exports)?.iter() {
-                    variables.insert(name.to_string(), value.clone());
+                    bind(name, value.clone())?;
                 }
             }
-            _ => {
-                return Err(Error::invalid_type(
-                    "import mapping (string or null)",
-                    &exports,
-                ))
-            }
         };
     }
-    let child_env = Env::new(variables, Some(env.clone()));
-    eval(&child_env, get_key(object, "+in")?)
+    Ok(variables)
 }
 
-pub fn export(env: &Arc<Env>, _object: &Object, args: &Value) -> Result<Value, Error> {
-    let object = Value::as_object(args)?;
-    Ok(Value::Object(eval_object(env, object)?))
+// Like `load_imports`, for the `import` form, whose names must not already be
+// bound by the program where it is used. They may shadow builtins and the
+// prelude.
+pub fn import_modules(env: &Arc<Env>, args: &Value) -> Result<Variables, Error> {
+    let variables = load_imports(env, args)?;
+    if let Some(name) = variables.keys().find(|name| env.binds(name)) {
+        return Err(Error::NameCollision(name.clone()));
+    }
+    Ok(variables)
+}
+
+// Returns the exports of an `export` form, which are its own fields followed
+// by the names it re-exports from the `import` mapping `from`.
+pub fn export_with(env: &Arc<Env>, exports: Value, from: &Value) -> Result<Value, Error> {
+    let mut exports = ObjectMap::clone(Value::as_object(&exports)?);
+    for (name, value) in load_imports(env, from)? {
+        if exports.contains_key(name.as_str()) {
+            return Err(Error::NameCollision(name));
+        }
+        exports.insert(Str::key(&name), value);
+    }
+    Ok(Value::Object(Arc::new(exports)))
 }
 
 pub fn map(env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
     let args = Value::as_array(args)?;
     let func = Value::as_function(get_index(args, 0)?)?;
     let array = Value::as_array(get_index(args, 1)?)?;
-
+    env.check_length(array.len())?;
     let results = array
         .iter()
         .map(|value| func.call(env, value))
@@ -128,25 +296,10 @@ pub fn map(env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
     Ok(Value::Array(Arc::new(results)))
 }
 
-pub fn if_func(env: &Arc<Env>, object: &Object, args: &Value) -> Result<Value, Error> {
-    let condition = Value::as_bool(&eval(env, args)?)?;
-    if condition {
-        eval(env, get_key(object, "+then")?)
-    } else {
-        eval(env, get_key(object, "+else")?)
-    }
+pub fn eq(lhs: Value, rhs: Value) -> bool {
+    lhs == rhs
 }
 
-pub fn eq(_env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
-    let args = Value::as_array(args)?;
-    let lhs = get_index(args, 0)?;
-    let rhs = get_index(args, 1)?;
-    Ok(Value::Bool(lhs == rhs))
-}
-
-pub fn plus(_env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
-    let args = Value::as_array(args)?;
-    let lhs = Value::as_f64(get_index(args, 0)?)?;
-    let rhs = Value::as_f64(get_index(args, 1)?)?;
-    Ok(Value::Number(Number::from_f64(lhs + rhs).unwrap()))
+pub fn plus(lhs: f64, rhs: f64) -> f64 {
+    lhs + rhs
 }
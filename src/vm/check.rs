@@ -0,0 +1,750 @@
+use super::*;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+// A problem found by `check`. The path is a JSON pointer into the program
+// stored in `file`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub file: String,
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}#{}: {}", self.file, self.path, self.message)
+    }
+}
+
+// Statically checks a parsed program without running it, following imports.
+// Reports unbound names, malformed operations, misspelled or missing `+` keys
+// on special forms, and malformed patterns, formals, and types.
+pub fn check(file: &str, program: &Value) -> Vec<Problem> {
+    let modules = Arc::new(Modules::new(file, Vec::new()));
+    check_with_modules(file, program, modules, false)
+}
+
+// Like `check`, resolving imports with `modules`, for a program that runs with
+// the standard prelude bound when `prelude` is set.
+pub fn check_with_modules(
+    file: &str,
+    program: &Value,
+    modules: Arc<Modules>,
+    prelude: bool,
+) -> Vec<Problem> {
+    let mut checker = Checker {
+        file: file.to_string(),
+        modules,
+        ..Checker::default()
+    };
+    if let Ok(path) = fs::canonicalize(file) {
+        checker.visited.insert(path);
+    }
+    let global = Scope::global(file, checker.modules.bindings());
+    let mut names = Map::new();
+    if prelude {
+        for name in prelude_names() {
+            names.insert(name, Binding::Value);
+        }
+    }
+    let mut scope = global.child(names);
+    scope.provided = true;
+    checker.check_expr(program, "", &scope);
+    checker.problems
+}
+
+fn prelude_names() -> Vec<String> {
+    let (_, source) = stdlib::find(stdlib::PRELUDE).unwrap();
+    parse(source)
+        .ok()
+        .and_then(|program| exports_of(&program))
+        .unwrap_or_default()
+}
+
+// Like `check_with_modules`, for a program that will run with `variables`
+// bound on top of the builtins.
+pub fn check_with_variables(
+    file: &str,
+    program: &Value,
+    variables: &Variables,
+    modules: Arc<Modules>,
+) -> Vec<Problem> {
+    let mut checker = Checker {
+        file: file.to_string(),
+        modules,
+        ..Checker::default()
+    };
+    if let Ok(path) = fs::canonicalize(file) {
+        checker.visited.insert(path);
+    }
+    let global = Scope::global(file, checker.modules.bindings());
+    let names = variables
+        .iter()
+        .map(|(name, value)| (name.clone(), Binding::of_value(value)))
+        .collect();
+    checker.check_expr(program, "", &global.child(names));
+    checker.problems
+}
+
+// The required and optional `+` keys of each special form.
+fn special_form_keys(name: &str) -> Option<(&'static [&'static str], &'static [&'static str])> {
+    match name {
+        "$" | "quote" | "quasiquote" => Some((&[], &[])),
+        "export" => Some((&[], &["+from"])),
+        "compile" => Some((&[], &["+env"])),
+        "lambda" => Some((&["+in"], &["+types", "+returns"])),
+        "let" | "import" => Some((&["+in"], &[])),
+        "if" => Some((&["+then", "+else"], &[])),
+        "case" => Some((&["+of"], &["+default"])),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Binding {
+    SpecialForm,
+    Singleton,
+    Positional,
+    Named,
+    Value,
+}
+
+impl Binding {
+    fn of_formals(formals: &Formals) -> Binding {
+        match formals {
+            Formals::Singleton(_) => Binding::Singleton,
+            Formals::Positional(_) => Binding::Positional,
+            Formals::Named(_) => Binding::Named,
+        }
+    }
+
+    fn of_value(value: &Value) -> Binding {
+        match value {
+            Value::Function(function) => match &function.body {
+                FunctionBody::NativeSpecialForm(_) | FunctionBody::SpecialForm(_) => {
+                    Binding::SpecialForm
+                }
+                FunctionBody::Lambda(lambda) => Binding::of_formals(&lambda.code.formals),
+                FunctionBody::Native(native) => match native.shape() {
+                    Shape::Singleton => Binding::Singleton,
+                    Shape::Positional { .. } => Binding::Positional,
+                    Shape::Named { .. } => Binding::Named,
+                },
+            },
+            _ => Binding::Value,
+        }
+    }
+}
+
+// A lexical scope. An open scope may contain names that cannot be known
+// statically, such as those imported from a module whose exports are computed.
+struct Scope<'a> {
+    names: Map<String, Binding>,
+    open: bool,
+    // Whether the names are provided to the program, like the builtins and the
+    // prelude, rather than bound by it, as for `Env`. Imports may shadow them.
+    provided: bool,
+    parent: Option<&'a Scope<'a>>,
+}
+
+impl<'a> Scope<'a> {
+    // The scope of the builtins and the bindings of the host.
+    fn global(file: &str, bindings: &Variables) -> Scope<'static> {
+        let env = Env::builtin(file.to_string());
+        let names = env
+            .variables
+            .iter()
+            .chain(bindings.iter())
+            .map(|(name, value)| (name.clone(), Binding::of_value(value)))
+            .collect();
+        Scope {
+            names,
+            open: false,
+            provided: true,
+            parent: None,
+        }
+    }
+
+    fn child(&'a self, names: Map<String, Binding>) -> Scope<'a> {
+        Scope {
+            names,
+            open: false,
+            provided: false,
+            parent: Some(self),
+        }
+    }
+
+    // Whether `name` is bound by the program, rather than only provided to it.
+    fn binds(&self, name: &str) -> bool {
+        (!self.provided && self.names.contains_key(name))
+            || self.parent.is_some_and(|parent| parent.binds(name))
+    }
+
+    fn lookup(&self, name: &str) -> Option<Binding> {
+        match self.names.get(name) {
+            Some(binding) => Some(*binding),
+            None => self.parent.and_then(|parent| parent.lookup(name)),
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.open || self.parent.is_some_and(|parent| parent.is_open())
+    }
+}
+
+#[derive(Default)]
+struct Checker {
+    problems: Vec<Problem>,
+    visited: HashSet<PathBuf>,
+    file: String,
+    modules: Arc<Modules>,
+}
+
+impl Checker {
+    fn report(&mut self, path: &str, message: String) {
+        self.problems.push(Problem {
+            file: self.file.clone(),
+            path: path.to_string(),
+            message,
+        });
+    }
+
+    fn check_module(&mut self, file: &str, program: &Value) {
+        let outer_file = std::mem::replace(&mut self.file, file.to_string());
+        let scope = Scope::global(file, self.modules.bindings());
+        self.check_expr(program, "", &scope);
+        self.file = outer_file;
+    }
+
+    fn check_expr(&mut self, value: &Value, path: &str, scope: &Scope) {
+        match value {
+            Value::Array(values) => {
+                for (index, value) in values.iter().enumerate() {
+                    self.check_expr(value, &pointer_child(path, &index.to_string()), scope);
+                }
+            }
+            Value::Object(object) => self.check_operation(object, path, scope),
+            _ => {}
+        }
+    }
+
+    fn check_operation(&mut self, object: &Object, path: &str, scope: &Scope) {
+        let ops: Vec<&Str> = object.keys().filter(|key| is_op_key(key)).collect();
+        let name = match ops.as_slice() {
+            [name] => name.as_str(),
+            [] => {
+                self.report(path, "Missing operation".to_string());
+                return;
+            }
+            _ => {
+                let mut names: Vec<&str> = ops.iter().map(|name| name.as_str()).collect();
+                names.sort();
+                self.report(path, format!("Ambiguous operation: {}", names.join(", ")));
+                return;
+            }
+        };
+        let args = &object[name];
+        let args_path = pointer_child(path, name);
+        match scope.lookup(name) {
+            Some(Binding::SpecialForm) => self.check_special_form(name, object, path, scope),
+            Some(Binding::Singleton) | Some(Binding::Positional) => {
+                self.check_expr(args, &args_path, scope)
+            }
+            Some(Binding::Named) => self.check_named_args(args, &args_path, scope),
+            Some(Binding::Value) => self.check_unknown_args(args, &args_path, scope),
+            None => {
+                if !scope.is_open() {
+                    self.report(path, format!("Undefined symbol `{}`", name));
+                }
+                self.check_unknown_args(args, &args_path, scope);
+            }
+        }
+    }
+
+    fn check_named_args(&mut self, args: &Value, path: &str, scope: &Scope) {
+        match args {
+            Value::Object(object) => {
+                for (name, value) in object.iter() {
+                    self.check_expr(value, &pointer_child(path, name), scope);
+                }
+            }
+            _ => self.report(
+                path,
+                format!("Expected named arguments, got {}", args.type_of()),
+            ),
+        }
+    }
+
+    // When the callee is not known statically, an object argument may be
+    // either an operation or a set of named arguments. Treat it as an
+    // operation only when it names something in scope.
+    fn check_unknown_args(&mut self, args: &Value, path: &str, scope: &Scope) {
+        if let Value::Object(object) = args {
+            let ops: Vec<&Str> = object.keys().filter(|key| is_op_key(key)).collect();
+            let is_operation = ops.len() == 1 && scope.lookup(ops[0]).is_some();
+            if !is_operation {
+                self.check_named_args(args, path, scope);
+                return;
+            }
+        }
+        self.check_expr(args, path, scope);
+    }
+
+    fn check_keys(&mut self, name: &str, object: &Object, path: &str) {
+        let (required, optional) = match special_form_keys(name) {
+            Some(keys) => keys,
+            None => return,
+        };
+        for key in required.iter() {
+            if !object.contains_key(*key) {
+                self.report(path, format!("`{}` requires `{}`", name, key));
+            }
+        }
+        let mut unexpected: Vec<&Str> = object
+            .keys()
+            .filter(|key| key.as_str() != name)
+            .filter(|key| !required.contains(&key.as_str()) && !optional.contains(&key.as_str()))
+            .collect();
+        unexpected.sort();
+        for key in unexpected {
+            self.report(
+                &pointer_child(path, key),
+                format!("Unexpected key `{}` for `{}`", key, name),
+            );
+        }
+    }
+
+    fn check_special_form(&mut self, name: &str, object: &Object, path: &str, scope: &Scope) {
+        self.check_keys(name, object, path);
+        let args = &object[name];
+        let args_path = pointer_child(path, name);
+        let key_path = |key: &str| pointer_child(path, key);
+        match name {
+            "$" => match args {
+                Value::String(symbol) => {
+                    if scope.lookup(symbol).is_none() && !scope.is_open() {
+                        self.report(&args_path, format!("Undefined symbol `{}`", symbol));
+                    }
+                }
+                _ => self.report(
+                    &args_path,
+                    format!("Expected a symbol name, got {}", args.type_of()),
+                ),
+            },
+            "quote" => {}
+            "quasiquote" => self.check_template(args, &args_path, 0, scope),
+            "compile" => {
+                self.check_expr(args, &args_path, scope);
+                if let Some(env) = object.get("+env") {
+                    self.check_expr(env, &key_path("+env"), scope);
+                }
+            }
+            "export" => {
+                self.check_named_args(args, &args_path, scope);
+                match object.get("+from") {
+                    Some(Value::Object(modules)) => {
+                        let from_path = key_path("+from");
+                        let (names, _) = self.check_import_mapping(modules, &from_path);
+                        for name in names {
+                            if let Value::Object(exports) = args {
+                                if exports.contains_key(name.as_str()) {
+                                    let message = format!("`{}` is exported more than once", name);
+                                    self.report(&from_path, message);
+                                }
+                            }
+                        }
+                    }
+                    Some(from) => self.report(
+                        &key_path("+from"),
+                        format!("Expected modules, got {}", from.type_of()),
+                    ),
+                    None => {}
+                }
+            }
+            "if" => {
+                self.check_expr(args, &args_path, scope);
+                for key in ["+then", "+else"] {
+                    if let Some(branch) = object.get(key) {
+                        self.check_expr(branch, &key_path(key), scope);
+                    }
+                }
+            }
+            "lambda" => self.check_lambda(object, path, scope),
+            "let" => self.check_let(object, path, scope),
+            "case" => self.check_case(object, path, scope),
+            "import" => self.check_import(object, path, scope),
+            _ => {}
+        }
+    }
+
+    // Checks the holes of a `quasiquote` template, which are expressions.
+    fn check_template(&mut self, value: &Value, path: &str, level: usize, scope: &Scope) {
+        let (object, marker) = match value {
+            Value::Array(values) => {
+                for (index, value) in values.iter().enumerate() {
+                    self.check_template(
+                        value,
+                        &pointer_child(path, &index.to_string()),
+                        level,
+                        scope,
+                    );
+                }
+                return;
+            }
+            Value::Object(object) => (object, object.keys().next().map(|key| key.as_str())),
+            _ => return,
+        };
+        let level = match marker {
+            Some(compile::QUASIQUOTE) if object.len() == 1 => level + 1,
+            Some(marker @ (compile::UNQUOTE | compile::UNQUOTE_SPLICING)) if object.len() == 1 => {
+                if level == 0 {
+                    self.check_expr(&object[marker], &pointer_child(path, marker), scope);
+                    return;
+                }
+                level - 1
+            }
+            _ => level,
+        };
+        for (key, value) in object.iter() {
+            self.check_template(value, &pointer_child(path, key), level, scope);
+        }
+    }
+
+    fn check_lambda(&mut self, object: &Object, path: &str, scope: &Scope) {
+        let args_path = pointer_child(path, "lambda");
+        let formals = match builtins::get_formals(&object["lambda"]) {
+            Ok(formals) => formals,
+            Err(err) => {
+                self.report(&args_path, format!("Invalid formals: {:?}", err));
+                return;
+            }
+        };
+        if let Err(err) = builtins::get_parameter_types(object, &formals) {
+            self.report(
+                &pointer_child(path, "+types"),
+                format!("Invalid parameter types: {:?}", err),
+            );
+        }
+        if let Some(Err(err)) = object.get("+returns").map(Type::parse) {
+            self.report(
+                &pointer_child(path, "+returns"),
+                format!("Invalid result type: {:?}", err),
+            );
+        }
+        let names = formals
+            .names()
+            .iter()
+            .map(|name| (name.to_string(), Binding::Value))
+            .collect();
+        let body_scope = scope.child(names);
+        if let Some(body) = object.get("+in") {
+            self.check_expr(body, &pointer_child(path, "+in"), &body_scope);
+        }
+    }
+
+    // Returns how a value bound by `let` can be called, if it is a lambda.
+    fn binding_of(value: &Value, scope: &Scope) -> Binding {
+        if let Value::Object(object) = value {
+            if let Some(formals) = object.get("lambda") {
+                if scope.lookup("lambda") == Some(Binding::SpecialForm) {
+                    if let Ok(formals) = builtins::get_formals(formals) {
+                        return Binding::of_formals(&formals);
+                    }
+                }
+            }
+        }
+        Binding::Value
+    }
+
+    fn check_let(&mut self, object: &Object, path: &str, scope: &Scope) {
+        let args_path = pointer_child(path, "let");
+        let bindings = match &object["let"] {
+            Value::Object(bindings) => bindings,
+            args => {
+                self.report(
+                    &args_path,
+                    format!("Expected bindings, got {}", args.type_of()),
+                );
+                return;
+            }
+        };
+        let mut names = Map::new();
+        for (name, value) in bindings.iter() {
+            let binding_path = pointer_child(&args_path, name);
+            match builtins::get_binding_pattern(value) {
+                Ok(Some((pattern, value))) => {
+                    self.check_expr(value, &pointer_child(&binding_path, "+value"), scope);
+                    names.insert(name.to_string(), Self::binding_of(value, scope));
+                    for name in pattern.names() {
+                        names.insert(name.to_string(), Binding::Value);
+                    }
+                }
+                Ok(None) => {
+                    self.check_expr(value, &binding_path, scope);
+                    names.insert(name.to_string(), Self::binding_of(value, scope));
+                }
+                Err(err) => self.report(&binding_path, format!("Invalid pattern: {:?}", err)),
+            }
+        }
+        let body_scope = scope.child(names);
+        if let Some(body) = object.get("+in") {
+            self.check_expr(body, &pointer_child(path, "+in"), &body_scope);
+        }
+    }
+
+    fn check_case(&mut self, object: &Object, path: &str, scope: &Scope) {
+        self.check_expr(&object["case"], &pointer_child(path, "case"), scope);
+        if let Some(default) = object.get("+default") {
+            self.check_expr(default, &pointer_child(path, "+default"), scope);
+        }
+        let branches_path = pointer_child(path, "+of");
+        let branches = match object.get("+of") {
+            Some(Value::Array(branches)) => branches,
+            Some(branches) => {
+                self.report(
+                    &branches_path,
+                    format!("Expected a list of branches, got {}", branches.type_of()),
+                );
+                return;
+            }
+            None => return,
+        };
+        for (index, branch) in branches.iter().enumerate() {
+            let branch_path = pointer_child(&branches_path, &index.to_string());
+            let branch = match branch {
+                Value::Object(branch) => branch,
+                _ => {
+                    self.report(
+                        &branch_path,
+                        format!("Expected a branch, got {}", branch.type_of()),
+                    );
+                    continue;
+                }
+            };
+            for key in ["+pattern", "+then"] {
+                if !branch.contains_key(key) {
+                    self.report(&branch_path, format!("Branch requires `{}`", key));
+                }
+            }
+            for key in branch.keys() {
+                if !matches!(key.as_str(), "+pattern" | "+when" | "+then") {
+                    self.report(
+                        &pointer_child(&branch_path, key),
+                        format!("Unexpected key `{}` for a branch", key),
+                    );
+                }
+            }
+            let mut names = Map::new();
+            if let Some(pattern) = branch.get("+pattern") {
+                match Pattern::parse(pattern) {
+                    Ok(pattern) => {
+                        for name in pattern.names() {
+                            names.insert(name.to_string(), Binding::Value);
+                        }
+                    }
+                    Err(err) => self.report(
+                        &pointer_child(&branch_path, "+pattern"),
+                        format!("Invalid pattern: {:?}", err),
+                    ),
+                }
+            }
+            let branch_scope = scope.child(names);
+            for key in ["+when", "+then"] {
+                if let Some(value) = branch.get(key) {
+                    self.check_expr(value, &pointer_child(&branch_path, key), &branch_scope);
+                }
+            }
+        }
+    }
+
+    fn check_import(&mut self, object: &Object, path: &str, scope: &Scope) {
+        let args_path = pointer_child(path, "import");
+        let modules = match &object["import"] {
+            Value::Object(modules) => modules,
+            args => {
+                self.report(
+                    &args_path,
+                    format!("Expected modules, got {}", args.type_of()),
+                );
+                return;
+            }
+        };
+        let (imported, open) = self.check_import_mapping(modules, &args_path);
+        let mut names = Map::new();
+        for name in imported {
+            if scope.binds(&name) {
+                self.report(&args_path, format!("`{}` is already bound", name));
+            }
+            names.insert(name, Binding::Value);
+        }
+        let mut body_scope = scope.child(names);
+        body_scope.open = open;
+        if let Some(body) = object.get("+in") {
+            self.check_expr(body, &pointer_child(path, "+in"), &body_scope);
+        }
+    }
+
+    // Checks the modules of an `import` mapping and returns the names they bind,
+    // and whether they may bind names that can't be determined statically.
+    fn check_import_mapping(&mut self, modules: &Object, path: &str) -> (Vec<String>, bool) {
+        let mut names = Vec::new();
+        let mut open = false;
+        for (name, mapping) in modules.iter() {
+            let module_path = pointer_child(path, name);
+            let spec = match builtins::get_import_spec(mapping) {
+                Ok(spec) => spec,
+                Err(err) => {
+                    self.report(&module_path, format!("Invalid import mapping: {:?}", err));
+                    continue;
+                }
+            };
+            let exports = self.check_imported_module(name, spec.format, &module_path);
+            let imported = match (&spec.names, exports) {
+                (Some(selected), exports) => {
+                    for (export, _) in selected.iter() {
+                        if exports
+                            .as_ref()
+                            .is_some_and(|exports| !exports.contains(&export.to_string()))
+                        {
+                            self.report(
+                                &module_path,
+                                format!("Module does not export `{}`", export),
+                            );
+                        }
+                    }
+                    Some(
+                        selected
+                            .iter()
+                            .map(|(_, local)| local.to_string())
+                            .collect(),
+                    )
+                }
+                (None, exports) => exports,
+            };
+            let imported = match (spec.binding, imported) {
+                (Some(alias), _) => vec![alias.to_string()],
+                (None, Some(imported)) => imported,
+                (None, None) => {
+                    open = true;
+                    Vec::new()
+                }
+            };
+            for name in imported {
+                if names.contains(&name) {
+                    self.report(
+                        &module_path,
+                        format!("`{}` is imported more than once", name),
+                    );
+                } else {
+                    names.push(name);
+                }
+            }
+        }
+        (names, open)
+    }
+
+    // Checks an imported module, if it has not been checked already, and
+    // returns its exports if they can be determined statically. Data files are
+    // read, and export their keys.
+    fn check_imported_module(
+        &mut self,
+        name: &str,
+        format: Option<Format>,
+        path: &str,
+    ) -> Option<Vec<String>> {
+        let location = match self.modules.resolve(&self.file, name) {
+            Ok(location) => location,
+            Err(Error::ModuleNotFound(_, tried)) => {
+                let tried = tried.join(", ");
+                self.report(path, format!("Cannot find module (tried {})", tried));
+                return None;
+            }
+            Err(err) => {
+                self.report(path, format!("Cannot find module: {:?}", err));
+                return None;
+            }
+        };
+        if let Some(exports) = self.modules.native_exports(&location) {
+            return match (format, exports) {
+                (None | Some(Format::Program), Value::Object(exports)) => {
+                    Some(exports.keys().map(|key| key.to_string()).collect())
+                }
+                (None | Some(Format::Program), _) => None,
+                _ => {
+                    let message = format!("Cannot read native module {} as data", location);
+                    self.report(path, message);
+                    None
+                }
+            };
+        }
+        let source = match location.source() {
+            Ok(source) => source,
+            Err(_) => {
+                self.report(path, format!("Cannot read module {}", location));
+                return None;
+            }
+        };
+        let format = format.unwrap_or_else(|| location.default_format());
+        if format != Format::Program {
+            return match data::parse_data(&source, format) {
+                Ok(Value::Object(object)) => {
+                    Some(object.keys().map(|key| key.to_string()).collect())
+                }
+                Ok(_) => None,
+                Err(_) => {
+                    self.report(path, format!("Cannot parse data {}", location));
+                    None
+                }
+            };
+        }
+        let program = match parse(&source) {
+            Ok(program) => program,
+            Err(_) => {
+                self.report(path, format!("Cannot parse module {}", location));
+                return None;
+            }
+        };
+        // The standard library is checked on its own, with the natives it is
+        // built on bound, by checking it by name.
+        if let Location::File(module_file) = &location {
+            let canonical = fs::canonicalize(module_file).unwrap_or_else(|_| module_file.clone());
+            if self.visited.insert(canonical) {
+                self.check_module(&module_file.display().to_string(), &program);
+            }
+        }
+        exports_of(&program)
+    }
+}
+
+// Finds the names a module exports when its result is an `export` form,
+// possibly nested in the bodies of `let` and `import`. Names re-exported from
+// modules are only known when they are listed.
+fn exports_of(program: &Value) -> Option<Vec<String>> {
+    let object = match program {
+        Value::Object(object) => object,
+        _ => return None,
+    };
+    if let Some(Value::Object(exports)) = object.get("export") {
+        let mut names: Vec<String> = exports.keys().map(|key| key.to_string()).collect();
+        if let Some(from) = object.get("+from") {
+            for mapping in Value::as_object(from).ok()?.values() {
+                let spec = builtins::get_import_spec(mapping).ok()?;
+                match (spec.binding, spec.names) {
+                    (Some(alias), _) => names.push(alias.to_string()),
+                    (None, Some(selected)) => {
+                        names.extend(selected.iter().map(|(_, local)| local.to_string()))
+                    }
+                    (None, None) => return None,
+                }
+            }
+        }
+        return Some(names);
+    }
+    if object.contains_key("let") || object.contains_key("import") {
+        return exports_of(object.get("+in")?);
+    }
+    None
+}
@@ -0,0 +1,786 @@
+use super::*;
+use std::cell::Cell;
+use std::sync::OnceLock;
+
+// Programs are compiled from `Value`s into a tree of `Node`s before they run.
+// Compilation resolves each operation once, pulls the arguments of special
+// forms out of their objects, and turns variable references into lexical
+// addresses (a depth in the `Env` chain and a slot within that `Env`) so that
+// running a program does not rescan objects or look up names by string.
+//
+// Problems found while compiling, such as a missing `+in`, are compiled into
+// `Node::Error` so they are reported only if that part of the program runs.
+
+// The special forms the compiler understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Form {
+    Lookup,
+    Quote,
+    If,
+    Let,
+    Lambda,
+    Case,
+    Import,
+    Export,
+    Quasiquote,
+    Compile,
+}
+
+// The markers that select the holes of a `quasiquote` template.
+pub const QUASIQUOTE: &str = "quasiquote";
+pub const UNQUOTE: &str = "unquote";
+pub const UNQUOTE_SPLICING: &str = "unquote_splicing";
+
+#[derive(Debug)]
+pub struct LambdaCode {
+    pub formals: Formals,
+    pub parameter_types: Vec<(Arc<String>, Type)>,
+    pub result_type: Option<Type>,
+    pub body: Node,
+}
+
+#[derive(Debug)]
+pub enum Node {
+    Constant(Value),
+    Error(Error),
+    Array(Vec<Node>),
+    Spliced(Vec<Element>),
+    Object(Vec<(Str, Node)>),
+    Local(usize, usize),
+    Named(usize, Arc<String>),
+    If(Box<Node>, Box<Node>, Box<Node>),
+    Let(Vec<(Binder, Node)>, Box<Node>),
+    Lambda(Arc<LambdaCode>),
+    Case(Box<Case>),
+    Import(Value, Box<Node>),
+    Export(Box<Node>, Value),
+    Compile(Box<Node>, Option<Box<Node>>),
+    Call(Box<Call>),
+}
+
+// An element of an array template, which is either a single value or an
+// array whose elements are spliced in.
+#[derive(Debug)]
+pub enum Element {
+    Item(Node),
+    Splice(Node),
+}
+
+#[derive(Debug)]
+pub enum Binder {
+    Name(String),
+    // Binds the name and destructures the value with the pattern.
+    Pattern(String, Pattern),
+}
+
+#[derive(Debug)]
+pub struct Case {
+    value: Node,
+    branches: Vec<Branch>,
+    default: Option<Node>,
+}
+
+#[derive(Debug)]
+struct Branch {
+    pattern: Pattern,
+    guard: Option<Node>,
+    body: Node,
+}
+
+#[derive(Debug)]
+pub struct Call {
+    callee: Node,
+    object: Object,
+    raw_args: Value,
+    args: Args,
+    form: Option<Box<Deferred>>,
+}
+
+// A special form reached through a callee that isn't known at compile time,
+// such as `if` in the body of an `import`. It is compiled the first time the
+// call runs, in the scope of the call, and kept for later runs.
+#[derive(Debug)]
+struct Deferred {
+    scope: Scope,
+    compiled: OnceLock<(Form, Node)>,
+}
+
+// How the arguments of a call are evaluated. When the callee isn't known at
+// compile time, an object argument could be either an operation or a set of
+// named arguments, so it is compiled once the callee is known.
+#[derive(Debug)]
+enum Args {
+    Expression(Node),
+    Named(Vec<(Str, Node)>),
+    Dynamic(Box<Dynamic>),
+}
+
+// The arguments of a call whose callee isn't known at compile time. Each
+// reading of the arguments is compiled the first time a callee needs it, in
+// the scope of the call, and kept for later calls.
+#[derive(Debug)]
+struct Dynamic {
+    args: Object,
+    scope: Scope,
+    expression: OnceLock<Node>,
+    named: OnceLock<Vec<(Str, Node)>>,
+}
+
+// How a callee known at compile time takes its arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Expression,
+    Positional,
+    Named,
+    Unknown,
+}
+
+impl Kind {
+    fn of_formals(formals: &Formals) -> Kind {
+        match formals {
+            Formals::Singleton(_) => Kind::Expression,
+            Formals::Positional(_) => Kind::Positional,
+            Formals::Named(_) => Kind::Named,
+        }
+    }
+
+    fn of_function(function: &Function) -> Kind {
+        match &function.body {
+            FunctionBody::Native(native) => match native.shape() {
+                Shape::Singleton => Kind::Expression,
+                Shape::Positional { .. } => Kind::Positional,
+                Shape::Named { .. } => Kind::Named,
+            },
+            FunctionBody::Lambda(lambda) => Kind::of_formals(&lambda.code.formals),
+            _ => Kind::Unknown,
+        }
+    }
+}
+
+// What the compiler knows about a variable.
+#[derive(Debug, Clone)]
+enum Binding {
+    Known(Value),
+    Lambda(Kind),
+    Unknown,
+}
+
+impl Binding {
+    fn of_node(node: &Node) -> Binding {
+        match node {
+            Node::Constant(value) => Binding::Known(value.clone()),
+            Node::Lambda(code) => Binding::Lambda(Kind::of_formals(&code.formals)),
+            _ => Binding::Unknown,
+        }
+    }
+}
+
+// A frame is static when the names it binds, and their order, are known at
+// compile time. An open frame, such as the one `import` creates, binds names
+// that are only known when the program runs.
+#[derive(Debug)]
+enum Frame {
+    Static(Vec<(String, Binding)>),
+    Open,
+}
+
+// Frames are shared, so that the arguments of a call can keep the scope they
+// are compiled in.
+#[derive(Debug, Clone)]
+struct Scope {
+    frames: Vec<Arc<Frame>>,
+}
+
+// Adds a name to a static frame. Binding a name twice reuses its first slot,
+// which matches how `Variables` orders repeated insertions.
+fn push_name(names: &mut Vec<(String, Binding)>, name: &str, binding: Binding) {
+    match names.iter_mut().find(|(existing, _)| existing == name) {
+        Some(entry) => entry.1 = binding,
+        None => names.push((name.to_string(), binding)),
+    }
+}
+
+fn unknown_frame(names: Vec<Arc<String>>) -> Frame {
+    let mut frame = Vec::new();
+    for name in names.iter() {
+        push_name(&mut frame, name, Binding::Unknown);
+    }
+    Frame::Static(frame)
+}
+
+impl Scope {
+    // Describes the frames of a running `Env`. Every variable in a running
+    // `Env` already has its value, so all of them are known.
+    fn of_env(env: &Env) -> Scope {
+        let mut frames = Vec::new();
+        let mut current = Some(env);
+        while let Some(env) = current {
+            let mut names = Vec::new();
+            for (name, value) in env.variables.iter() {
+                push_name(&mut names, name, Binding::Known(value.clone()));
+            }
+            frames.push(Arc::new(Frame::Static(names)));
+            current = env.parent.as_deref();
+        }
+        frames.reverse();
+        Scope { frames }
+    }
+
+    fn resolve(&self, name: &str) -> (Node, Binding) {
+        for (depth, frame) in self.frames.iter().rev().enumerate() {
+            match &**frame {
+                Frame::Static(names) => {
+                    if let Some(slot) = names.iter().position(|(existing, _)| existing == name) {
+                        let binding = names[slot].1.clone();
+                        let node = match &binding {
+                            Binding::Known(value) => Node::Constant(value.clone()),
+                            _ => Node::Local(depth, slot),
+                        };
+                        return (node, binding);
+                    }
+                }
+                Frame::Open => {
+                    return (
+                        Node::Named(depth, Arc::new(name.to_string())),
+                        Binding::Unknown,
+                    )
+                }
+            }
+        }
+        let err = Error::UndefinedSymbol(name.to_string());
+        (Node::Error(err), Binding::Unknown)
+    }
+
+    fn with_frame<T>(&mut self, frame: Frame, f: impl FnOnce(&mut Scope) -> T) -> T {
+        self.frames.push(Arc::new(frame));
+        let result = f(self);
+        self.frames.pop();
+        result
+    }
+}
+
+// Compiles `value` to run in `env`.
+pub fn compile(env: &Arc<Env>, value: &Value) -> Node {
+    compile_value(value, &mut Scope::of_env(env))
+}
+
+// Compiles and runs a special form that was not resolved at compile time, for
+// example one reached through a variable bound to it.
+pub fn run_form(form: Form, env: &Arc<Env>, object: &Object, args: &Value) -> Result<Value, Error> {
+    compile_form(form, object, args, &mut Scope::of_env(env)).run(env)
+}
+
+macro_rules! try_compile {
+    ($result:expr) => {
+        match $result {
+            Ok(value) => value,
+            Err(err) => return Node::Error(err),
+        }
+    };
+}
+
+fn compile_value(value: &Value, scope: &mut Scope) -> Node {
+    match value {
+        Value::Array(values) => {
+            let nodes = values
+                .iter()
+                .map(|value| compile_value(value, scope))
+                .collect();
+            fold_array(nodes)
+        }
+        Value::Object(object) => compile_operation(object, scope),
+        _ => Node::Constant(value.clone()),
+    }
+}
+
+fn constant(node: &Node) -> Option<Value> {
+    match node {
+        Node::Constant(value) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+// Builds an array, folding it into a constant when all of its elements are.
+fn fold_array(nodes: Vec<Node>) -> Node {
+    match nodes.iter().map(constant).collect::<Option<Vec<Value>>>() {
+        Some(values) => Node::Constant(Value::Array(Arc::new(values))),
+        None => Node::Array(nodes),
+    }
+}
+
+// Builds an object, folding it into a constant when all of its values are.
+fn fold_object(fields: Vec<(Str, Node)>) -> Node {
+    let constants = fields
+        .iter()
+        .map(|(name, node)| Some((name.clone(), constant(node)?)))
+        .collect::<Option<ObjectMap>>();
+    match constants {
+        Some(object) => Node::Constant(Value::Object(Arc::new(object))),
+        None => Node::Object(fields),
+    }
+}
+
+fn compile_key(object: &Object, key: &str, scope: &mut Scope) -> Node {
+    match object.get(key) {
+        Some(value) => compile_value(value, scope),
+        None => Node::Error(Error::UnknownKey(key.to_string())),
+    }
+}
+
+fn compile_fields(object: &Object, scope: &mut Scope) -> Vec<(Str, Node)> {
+    object
+        .iter()
+        .map(|(name, value)| (name.clone(), compile_value(value, scope)))
+        .collect()
+}
+
+fn compile_operation(object: &Object, scope: &mut Scope) -> Node {
+    let op = try_compile!(get_op(object));
+    let (callee, binding) = scope.resolve(&op.name);
+    let kind = match &binding {
+        Binding::Known(Value::Function(function)) => {
+            if let FunctionBody::SpecialForm(form) = function.body {
+                return compile_form(form, object, &op.args, scope);
+            }
+            Kind::of_function(function)
+        }
+        Binding::Lambda(kind) => *kind,
+        _ => Kind::Unknown,
+    };
+    let args = match (&op.args, kind) {
+        (Value::Object(object), Kind::Named) => Args::Named(compile_fields(object, scope)),
+        (Value::Object(_), Kind::Expression) => Args::Expression(compile_value(&op.args, scope)),
+        (Value::Object(args), _) => Args::Dynamic(Box::new(Dynamic {
+            args: args.clone(),
+            scope: scope.clone(),
+            expression: OnceLock::new(),
+            named: OnceLock::new(),
+        })),
+        _ => Args::Expression(compile_value(&op.args, scope)),
+    };
+    let form = (kind == Kind::Unknown).then(|| {
+        Box::new(Deferred {
+            scope: scope.clone(),
+            compiled: OnceLock::new(),
+        })
+    });
+    Node::Call(Box::new(Call {
+        callee,
+        object: object.clone(),
+        raw_args: op.args,
+        args,
+        form,
+    }))
+}
+
+thread_local! {
+    static COMPILED_FORMS: Cell<usize> = const { Cell::new(0) };
+}
+
+// The number of special forms compiled on this thread, so that tests can
+// check that a form is compiled once rather than on every run.
+#[doc(hidden)]
+pub fn compiled_forms() -> usize {
+    COMPILED_FORMS.with(Cell::get)
+}
+
+fn compile_form(form: Form, object: &Object, args: &Value, scope: &mut Scope) -> Node {
+    COMPILED_FORMS.with(|count| count.set(count.get() + 1));
+    match form {
+        // TODO: Support pathing operators.
+        Form::Lookup => scope.resolve(try_compile!(Value::as_str(args))).0,
+        Form::Quote => Node::Constant(args.clone()),
+        Form::If => Node::If(
+            Box::new(compile_value(args, scope)),
+            Box::new(compile_key(object, "+then", scope)),
+            Box::new(compile_key(object, "+else", scope)),
+        ),
+        Form::Let => compile_let(object, args, scope),
+        Form::Lambda => compile_lambda(object, args, scope),
+        Form::Case => compile_case(object, args, scope),
+        Form::Import => {
+            let body = scope.with_frame(Frame::Open, |scope| compile_key(object, "+in", scope));
+            Node::Import(args.clone(), Box::new(body))
+        }
+        Form::Export => {
+            let exports = Node::Object(compile_fields(try_compile!(Value::as_object(args)), scope));
+            match object.get("+from") {
+                Some(from) => Node::Export(Box::new(exports), from.clone()),
+                None => exports,
+            }
+        }
+        Form::Quasiquote => compile_template(args, 0, scope),
+        Form::Compile => Node::Compile(
+            Box::new(compile_value(args, scope)),
+            object
+                .get("+env")
+                .map(|env| Box::new(compile_value(env, scope))),
+        ),
+    }
+}
+
+// Returns the marker and argument of a template object such as
+// `{ unquote: ... }`.
+fn get_marker(object: &Object) -> Option<(&str, &Value)> {
+    let (key, value) = object.iter().next()?;
+    match key.as_str() {
+        QUASIQUOTE | UNQUOTE | UNQUOTE_SPLICING if object.len() == 1 => Some((key, value)),
+        _ => None,
+    }
+}
+
+// Compiles a `quasiquote` template. Everything but the `unquote` and
+// `unquote_splicing` holes is literal. Templates may nest; each nested
+// `quasiquote` adds a level, and a hole belongs to the template whose level it
+// brings back to zero, so inner templates are left for the code they build.
+fn compile_template(value: &Value, level: usize, scope: &mut Scope) -> Node {
+    match value {
+        Value::Array(values) => {
+            let mut elements = Vec::new();
+            for value in values.iter() {
+                let splice = match value {
+                    Value::Object(object) => match get_marker(object) {
+                        Some((UNQUOTE_SPLICING, args)) if level == 0 => Some(args),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                elements.push(match splice {
+                    Some(args) => Element::Splice(compile_value(args, scope)),
+                    None => Element::Item(compile_template(value, level, scope)),
+                });
+            }
+            if elements
+                .iter()
+                .any(|element| matches!(element, Element::Splice(_)))
+            {
+                return Node::Spliced(elements);
+            }
+            let nodes = elements
+                .into_iter()
+                .map(|element| match element {
+                    Element::Item(node) | Element::Splice(node) => node,
+                })
+                .collect();
+            fold_array(nodes)
+        }
+        Value::Object(object) => {
+            let level = match get_marker(object) {
+                Some((UNQUOTE, args)) if level == 0 => return compile_value(args, scope),
+                Some((UNQUOTE_SPLICING, _)) if level == 0 => {
+                    return Node::Error(Error::InvalidOperation(format!(
+                        "{} must be an element of an array",
+                        UNQUOTE_SPLICING
+                    )))
+                }
+                Some((QUASIQUOTE, _)) => level + 1,
+                Some(_) => level - 1,
+                None => level,
+            };
+            let fields = object
+                .iter()
+                .map(|(name, value)| (name.clone(), compile_template(value, level, scope)))
+                .collect();
+            fold_object(fields)
+        }
+        _ => Node::Constant(value.clone()),
+    }
+}
+
+// TODO: In this version of let, the values being bound to variables cannot see
+// themselves or other variables being bound. Eventually, we'll want letrec,
+// which will allow variables to see other variables, but involves a mutation
+// somewhere.
+fn compile_let(object: &Object, args: &Value, scope: &mut Scope) -> Node {
+    let bindings = try_compile!(Value::as_object(args));
+    let mut binders = Vec::new();
+    let mut names = Vec::new();
+    for (name, value) in bindings.iter() {
+        match try_compile!(builtins::get_binding_pattern(value)) {
+            Some((pattern, value)) => {
+                let node = compile_value(value, scope);
+                push_name(&mut names, name, Binding::of_node(&node));
+                for name in pattern.names() {
+                    push_name(&mut names, &name, Binding::Unknown);
+                }
+                binders.push((Binder::Pattern(name.to_string(), pattern), node));
+            }
+            None => {
+                let node = compile_value(value, scope);
+                push_name(&mut names, name, Binding::of_node(&node));
+                binders.push((Binder::Name(name.to_string()), node));
+            }
+        }
+    }
+    let body = scope.with_frame(Frame::Static(names), |scope| {
+        compile_key(object, "+in", scope)
+    });
+    Node::Let(binders, Box::new(body))
+}
+
+fn compile_lambda(object: &Object, args: &Value, scope: &mut Scope) -> Node {
+    let formals = try_compile!(builtins::get_formals(args));
+    let parameter_types = try_compile!(builtins::get_parameter_types(object, &formals));
+    let result_type = try_compile!(object.get("+returns").map(Type::parse).transpose());
+    let body = try_compile!(get_key(object, "+in"));
+    let body = scope.with_frame(unknown_frame(formals.names()), |scope| {
+        compile_value(body, scope)
+    });
+    Node::Lambda(Arc::new(LambdaCode {
+        formals,
+        parameter_types,
+        result_type,
+        body,
+    }))
+}
+
+fn compile_case(object: &Object, args: &Value, scope: &mut Scope) -> Node {
+    let value = compile_value(args, scope);
+    let mut branches = Vec::new();
+    for branch in try_compile!(Value::as_array(try_compile!(get_key(object, "+of")))).iter() {
+        let branch = try_compile!(Value::as_object(branch));
+        let pattern = try_compile!(Pattern::parse(try_compile!(get_key(branch, "+pattern"))));
+        let (guard, body) = scope.with_frame(unknown_frame(pattern.names()), |scope| {
+            let guard = branch.get("+when").map(|guard| compile_value(guard, scope));
+            (guard, compile_key(branch, "+then", scope))
+        });
+        branches.push(Branch {
+            pattern,
+            guard,
+            body,
+        });
+    }
+    let default = object
+        .get("+default")
+        .map(|default| compile_value(default, scope));
+    Node::Case(Box::new(Case {
+        value,
+        branches,
+        default,
+    }))
+}
+
+fn run_fields(fields: &[(Str, Node)], env: &Arc<Env>) -> Result<Object, Error> {
+    env.check_length(fields.len())?;
+    Ok(Arc::new(
+        fields
+            .iter()
+            .map(|(name, node)| Ok((name.clone(), node.run(env)?)))
+            .collect::<Result<ObjectMap, Error>>()?,
+    ))
+}
+
+impl Node {
+    pub fn run(&self, env: &Arc<Env>) -> Result<Value, Error> {
+        if let Some(budget) = &env.budget {
+            budget.step()?;
+        }
+        match self {
+            Node::Constant(value) => Ok(value.clone()),
+            Node::Error(err) => Err(err.clone()),
+            Node::Array(nodes) => {
+                env.check_length(nodes.len())?;
+                Ok(Value::Array(Arc::new(
+                    nodes
+                        .iter()
+                        .map(|node| node.run(env))
+                        .collect::<Result<Vec<Value>, Error>>()?,
+                )))
+            }
+            Node::Spliced(elements) => {
+                let mut values = Vec::new();
+                for element in elements.iter() {
+                    match element {
+                        Element::Item(node) => {
+                            let value = node.run(env)?;
+                            env.check_length(values.len() + 1)?;
+                            values.push(value);
+                        }
+                        Element::Splice(node) => {
+                            let spliced = node.run(env)?;
+                            let spliced = Value::as_array(&spliced)?;
+                            env.check_length(values.len() + spliced.len())?;
+                            env.check_deadline()?;
+                            values.extend(spliced.iter().cloned());
+                        }
+                    }
+                }
+                Ok(Value::Array(Arc::new(values)))
+            }
+            Node::Object(fields) => Ok(Value::Object(run_fields(fields, env)?)),
+            Node::Local(depth, slot) => Ok(env.ancestor(*depth).variables[*slot].clone()),
+            Node::Named(depth, name) => Ok(env.ancestor(*depth).lookup(name)?.clone()),
+            Node::If(condition, then, otherwise) => {
+                if Value::as_bool(&condition.run(env)?)? {
+                    then.run(env)
+                } else {
+                    otherwise.run(env)
+                }
+            }
+            Node::Let(binders, body) => {
+                let mut variables = Variables::new();
+                for (binder, node) in binders.iter() {
+                    let value = node.run(env)?;
+                    match binder {
+                        Binder::Name(name) => {
+                            variables.insert(name.clone(), value);
+                        }
+                        Binder::Pattern(name, pattern) => {
+                            variables.insert(name.clone(), value.clone());
+                            pattern.destructure(&value, &mut variables)?;
+                        }
+                    }
+                }
+                body.run(&Env::new(variables, Some(env.clone())))
+            }
+            Node::Lambda(code) => Ok(Value::Function(Arc::new(Function {
+                body: FunctionBody::Lambda(Lambda {
+                    env: env.clone(),
+                    code: code.clone(),
+                }),
+            }))),
+            Node::Case(case) => case.run(env),
+            Node::Import(modules, body) => {
+                let variables = builtins::import_modules(env, modules)?;
+                body.run(&Env::new(variables, Some(env.clone())))
+            }
+            Node::Export(exports, from) => builtins::export_with(env, exports.run(env)?, from),
+            Node::Compile(program, variables) => {
+                let program = program.run(env)?;
+                let variables = match variables {
+                    Some(variables) => Value::as_object(&variables.run(env)?)?
+                        .iter()
+                        .map(|(name, value)| (name.to_string(), value.clone()))
+                        .collect(),
+                    None => Variables::new(),
+                };
+                let file = Value::as_str(env.lookup(FILE_SYMBOL)?)?;
+                let root_env = Env::builtin_with_modules(file.to_string(), env.modules());
+                compile_function(root_env, &program, variables)
+            }
+            Node::Call(call) => call.run(env),
+        }
+    }
+}
+
+// Turns `program`, such as a `lambda` built at runtime, into a function. The
+// program only sees the builtins and `variables`, and is checked before it runs
+// so that a malformed program fails here rather than when it is called.
+fn compile_function(
+    root_env: Arc<Env>,
+    program: &Value,
+    variables: Variables,
+) -> Result<Value, Error> {
+    let file = Value::as_str(root_env.lookup(FILE_SYMBOL)?)?;
+    let problems = check::check_with_variables(file, program, &variables, root_env.modules());
+    if !problems.is_empty() {
+        let problems: Vec<String> = problems.iter().map(Problem::to_string).collect();
+        return Err(Error::InvalidProgram(problems.join("\n")));
+    }
+    let env = Env::new(variables, Some(root_env));
+    let function = eval(&env, program)?;
+    Value::as_function(&function)?;
+    Ok(function)
+}
+
+impl Case {
+    fn run(&self, env: &Arc<Env>) -> Result<Value, Error> {
+        let value = self.value.run(env)?;
+        for branch in self.branches.iter() {
+            let mut variables = Variables::new();
+            if !branch.pattern.bind(&value, &mut variables) {
+                continue;
+            }
+            let child_env = Env::new(variables, Some(env.clone()));
+            if let Some(guard) = &branch.guard {
+                if !Value::as_bool(&guard.run(&child_env)?)? {
+                    continue;
+                }
+            }
+            return branch.body.run(&child_env);
+        }
+        match &self.default {
+            Some(default) => default.run(env),
+            None => Err(Error::NoMatchingCase),
+        }
+    }
+}
+
+impl Call {
+    fn run(&self, env: &Arc<Env>) -> Result<Value, Error> {
+        let callee = self.callee.run(env)?;
+        let function = Value::as_function(&callee)?;
+        if let (FunctionBody::SpecialForm(form), Some(deferred)) = (&function.body, &self.form) {
+            return deferred.run(*form, &self.object, &self.raw_args, env);
+        }
+        match (&function.body, &self.args) {
+            (FunctionBody::Native(native), Args::Expression(args)) => {
+                native.call(env, &args.run(env)?)
+            }
+            (FunctionBody::Native(native), Args::Named(fields))
+                if matches!(native.shape(), Shape::Named { .. }) =>
+            {
+                native.call(env, &Value::Object(run_fields(fields, env)?))
+            }
+            (FunctionBody::Lambda(lambda), Args::Expression(args)) => {
+                match (&lambda.code.formals, &self.raw_args) {
+                    (Formals::Singleton(_), _) | (Formals::Positional(_), Value::Array(_)) => {
+                        lambda.call(&args.run(env)?)
+                    }
+                    _ => lambda.eval(env, &self.raw_args),
+                }
+            }
+            (FunctionBody::Lambda(lambda), Args::Named(fields)) => match lambda.code.formals {
+                Formals::Named(_) => lambda.call(&Value::Object(run_fields(fields, env)?)),
+                _ => lambda.eval(env, &self.raw_args),
+            },
+            (FunctionBody::Native(native), Args::Dynamic(dynamic)) => match native.shape() {
+                Shape::Named { .. } => native.call(env, &dynamic.run_named(env)?),
+                _ => native.call(env, &dynamic.run_expression(env)?),
+            },
+            (FunctionBody::Lambda(lambda), Args::Dynamic(dynamic)) => match lambda.code.formals {
+                Formals::Singleton(_) => lambda.call(&dynamic.run_expression(env)?),
+                Formals::Named(_) => lambda.call(&dynamic.run_named(env)?),
+                Formals::Positional(_) => lambda.eval(env, &self.raw_args),
+            },
+            _ => function.eval(env, &self.object, &self.raw_args),
+        }
+    }
+}
+
+impl Deferred {
+    fn run(
+        &self,
+        form: Form,
+        object: &Object,
+        args: &Value,
+        env: &Arc<Env>,
+    ) -> Result<Value, Error> {
+        let compile = || compile_form(form, object, args, &mut self.scope.clone());
+        let (compiled, node) = self.compiled.get_or_init(|| (form, compile()));
+        // The callee is only known at run time, so it may name another form
+        // on a later run.
+        if *compiled == form {
+            node.run(env)
+        } else {
+            compile().run(env)
+        }
+    }
+}
+
+impl Dynamic {
+    // Runs the arguments as an operation.
+    fn run_expression(&self, env: &Arc<Env>) -> Result<Value, Error> {
+        self.expression
+            .get_or_init(|| compile_operation(&self.args, &mut self.scope.clone()))
+            .run(env)
+    }
+
+    // Runs the arguments as named arguments.
+    fn run_named(&self, env: &Arc<Env>) -> Result<Value, Error> {
+        let fields = self
+            .named
+            .get_or_init(|| compile_fields(&self.args, &mut self.scope.clone()));
+        Ok(Value::Object(run_fields(fields, env)?))
+    }
+}
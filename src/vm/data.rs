@@ -0,0 +1,98 @@
+use super::*;
+use std::path::Path;
+
+// How an imported file is turned into a value. A `Program` is evaluated and
+// its exports imported; every other format is read as plain data, so that the
+// keys of its objects are never taken for operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    Program,
+    // A `.yapl` file read as YAML without evaluating it.
+    Data,
+    Json,
+    Yaml,
+    // One JSON value per line, read as an array.
+    Ndjson,
+    // A header row followed by records, read as an array of objects whose
+    // values are strings.
+    Csv,
+}
+
+impl Format {
+    // The format named by the `format` of an import.
+    pub fn named(name: &str) -> Option<Format> {
+        match name {
+            "yapl" => Some(Format::Program),
+            "data" => Some(Format::Data),
+            "json" => Some(Format::Json),
+            "yaml" => Some(Format::Yaml),
+            "ndjson" => Some(Format::Ndjson),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+
+    // The format of files with the extension of `path`, if it is known.
+    pub fn of_extension(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()? {
+            "yapl" => Some(Format::Program),
+            "json" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "ndjson" | "jsonl" => Some(Format::Ndjson),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+}
+
+// Parses `source` as data in `format`, which must not be a `Program`. Errors
+// say where the data is malformed.
+pub fn parse_data(source: &str, format: Format) -> Result<Value, Error> {
+    match format {
+        Format::Program => Err(Error::InvalidOperation(
+            "Cannot read a program as data".to_string(),
+        )),
+        Format::Data | Format::Yaml => serde_yaml::from_str(source).map_err(invalid_data),
+        Format::Json => serde_json::from_str(source).map_err(invalid_data),
+        Format::Ndjson => parse_ndjson(source),
+        Format::Csv => parse_csv(source),
+    }
+}
+
+fn invalid_data(err: impl std::fmt::Display) -> Error {
+    Error::InvalidData(err.to_string())
+}
+
+fn parse_ndjson(source: &str) -> Result<Value, Error> {
+    let values = source
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line)
+                .map_err(|err| invalid_data(format!("line {}: {}", index + 1, err)))
+        })
+        .collect::<Result<Vec<Value>, Error>>()?;
+    Ok(Value::Array(Arc::new(values)))
+}
+
+fn parse_csv(source: &str) -> Result<Value, Error> {
+    let mut reader = csv::Reader::from_reader(source.as_bytes());
+    let headers: Vec<Str> = reader
+        .headers()
+        .map_err(invalid_data)?
+        .iter()
+        .map(Str::key)
+        .collect();
+    let mut records = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(invalid_data)?;
+        let object: ObjectMap = headers
+            .iter()
+            .zip(record.iter())
+            .map(|(header, field)| (header.clone(), Value::String(Str::from(field))))
+            .collect();
+        records.push(Value::Object(Arc::new(object)));
+    }
+    Ok(Value::Array(Arc::new(records)))
+}
@@ -0,0 +1,283 @@
+use super::*;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{mpsc, Mutex};
+
+// The interpreter as embedded in a host application. The host binds its own
+// natives and values, which may be closures over its state, and runs programs
+// in a `Context`:
+//
+//     let mut engine = Engine::new();
+//     engine.bind_function("now", move |_env, _args| Ok(clock.now()));
+//     let result = engine.run_file("main.yapl")?;
+//
+// Every program and module the engine runs sees the host bindings on top of
+// the builtins. Hosts can also register native modules, which programs import
+// by name, to provide an API without binding it everywhere.
+#[derive(Default)]
+pub struct Engine {
+    // Directories to look up package-style imports in, before the directory
+    // of the program.
+    pub search_path: Vec<PathBuf>,
+    // Whether to bind the exports of `std/prelude` before a program runs.
+    pub prelude: bool,
+    host: Arc<Host>,
+}
+
+// A module provided by the host. Its exports are imported like those of a
+// module file.
+#[derive(Default)]
+pub struct NativeModule {
+    exports: ObjectMap,
+}
+
+// The state of one run of an `Engine`: the root `Env` of a program and the
+// modules it imported.
+pub struct Context {
+    env: Arc<Env>,
+    file: String,
+    prelude: bool,
+    worker: Mutex<Option<Worker>>,
+}
+
+// The thread a `Context` with limits runs programs on, whose stack holds the
+// deepest calls they allow. It is started by the first run and kept for later
+// ones, so that runs don't each make a stack. It stops once the `Context` is
+// dropped.
+struct Worker {
+    jobs: mpsc::Sender<Job>,
+    thread: std::thread::ThreadId,
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+impl Engine {
+    pub fn new() -> Engine {
+        Engine::default()
+    }
+
+    pub fn bind_function<F>(&mut self, name: &str, function: F)
+    where
+        F: Fn(&Arc<Env>, &Value) -> Result<Value, Error> + Send + Sync + 'static,
+    {
+        self.bind_function_with_shape(name, Shape::Singleton, function);
+    }
+
+    // Binds a native that takes arguments of `shape`, like a lambda with
+    // positional or named parameters.
+    pub fn bind_function_with_shape<F>(&mut self, name: &str, shape: Shape, function: F)
+    where
+        F: Fn(&Arc<Env>, &Value) -> Result<Value, Error> + Send + Sync + 'static,
+    {
+        self.bind_value(name, Function::native(name, shape, function));
+    }
+
+    // Binds a function with a typed Rust signature; see `TypedFunction`.
+    pub fn bind_typed_function<Args, F: TypedFunction<Args>>(&mut self, name: &str, function: F) {
+        self.bind_value(name, Function::typed(name, function));
+    }
+
+    pub fn bind_special_form<F>(&mut self, name: &str, special_form: F)
+    where
+        F: Fn(&Arc<Env>, &Object, &Value) -> Result<Value, Error> + Send + Sync + 'static,
+    {
+        self.bind_value(name, Function::native_special_form(special_form));
+    }
+
+    pub fn bind_value(&mut self, name: &str, value: Value) {
+        let host = Arc::make_mut(&mut self.host);
+        host.bindings.insert(name.to_string(), value);
+    }
+
+    // Limits what each run of a program may use, for running programs that
+    // aren't trusted.
+    pub fn set_limits(&mut self, limits: Limits) {
+        Arc::make_mut(&mut self.host).limits = limits;
+    }
+
+    // Makes `module` importable as `name`, which takes precedence over the
+    // standard library and module files of the same name.
+    pub fn register_module(&mut self, name: &str, module: NativeModule) {
+        let host = Arc::make_mut(&mut self.host);
+        let exports = Value::Object(Arc::new(module.exports));
+        host.modules.insert(name.to_string(), exports);
+    }
+
+    fn modules(&self, file: &str) -> Arc<Modules> {
+        let modules = Modules::with_host(file, self.search_path.clone(), self.host.clone());
+        Arc::new(modules)
+    }
+
+    // Creates a context for a program stored at `file`, which is where its
+    // imports are resolved from. The file isn't read.
+    pub fn context(&self, file: &str) -> Result<Context, Error> {
+        let env = Env::builtin_with_modules(file.to_string(), self.modules(file));
+        let env = if self.prelude {
+            env.with_prelude()?
+        } else {
+            env
+        };
+        Ok(Context {
+            env,
+            file: file.to_string(),
+            prelude: self.prelude,
+            worker: Mutex::new(None),
+        })
+    }
+
+    // Reads and runs the program stored at `file` in a new context.
+    pub fn run_file(&self, file: &str) -> Result<Value, Error> {
+        let source = fs::read_to_string(file).map_err(|_| Error::IO)?;
+        self.context(file)?.run(&source)
+    }
+
+    // Reads and checks the program stored at `file`, without running it. A
+    // `file` naming a module of the standard library, such as `std/list`,
+    // checks that module, with the natives it is built on bound.
+    pub fn check_file(&self, file: &str) -> Result<Vec<Problem>, Error> {
+        if let Some((name, source)) = stdlib::find(file) {
+            return Ok(check::check_with_variables(
+                name,
+                &parse(source)?,
+                &stdlib::intrinsics(),
+                self.modules(name),
+            ));
+        }
+        let source = fs::read_to_string(file).map_err(|_| Error::IO)?;
+        let program = parse(&source)?;
+        Ok(check::check_with_modules(
+            file,
+            &program,
+            self.modules(file),
+            self.prelude,
+        ))
+    }
+}
+
+impl Context {
+    pub fn env(&self) -> &Arc<Env> {
+        &self.env
+    }
+
+    // Parses and evaluates `source`.
+    pub fn run(&self, source: &str) -> Result<Value, Error> {
+        self.eval(&parse(source)?)
+    }
+
+    pub fn eval(&self, program: &Value) -> Result<Value, Error> {
+        let env = self.env.clone();
+        let program = program.clone();
+        self.start(move || eval(&env, &program))
+    }
+
+    // Calls `function`, such as one a program returned, with `args`.
+    pub fn call(&self, function: &Value, args: &Value) -> Result<Value, Error> {
+        let env = self.env.clone();
+        let (function, args) = (function.clone(), args.clone());
+        self.start(move || Value::as_function(&function)?.call(&env, &args))
+    }
+
+    // Starts a run. If there are limits, the run gets the whole budget, and
+    // runs on the worker of the context. A run started from the worker, such
+    // as by a native, runs where it is.
+    fn start<F>(&self, run: F) -> Result<Value, Error>
+    where
+        F: FnOnce() -> Result<Value, Error> + Send + 'static,
+    {
+        let budget = match &self.env.budget {
+            Some(budget) => budget,
+            None => return run(),
+        };
+        budget.start();
+        let mut worker = self.worker.lock().unwrap();
+        if worker.is_none() {
+            *worker = Some(Worker::spawn(budget.stack_size())?);
+        }
+        let jobs = match worker.as_ref() {
+            Some(worker) if worker.thread != std::thread::current().id() => worker.jobs.clone(),
+            _ => {
+                drop(worker);
+                return run();
+            }
+        };
+        drop(worker);
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let job: Job = Box::new(move || {
+            let _ = sender.send(panic::catch_unwind(AssertUnwindSafe(run)));
+        });
+        jobs.send(job).map_err(|_| Worker::stopped())?;
+        match receiver.recv().map_err(|_| Worker::stopped())? {
+            Ok(result) => result,
+            Err(panic) => panic::resume_unwind(panic),
+        }
+    }
+
+    // Checks `program` as if it were run in this context.
+    pub fn check(&self, program: &Value) -> Vec<Problem> {
+        check::check_with_modules(&self.file, program, self.env.modules(), self.prelude)
+    }
+}
+
+impl Worker {
+    fn spawn(stack_size: usize) -> Result<Worker, Error> {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let thread = std::thread::Builder::new()
+            .stack_size(stack_size)
+            .spawn(move || {
+                for job in queue {
+                    job();
+                }
+            })
+            .map_err(|err| {
+                let message = format!("Cannot make a stack for the call depth: {}", err);
+                Error::InvalidOperation(message)
+            })?;
+        Ok(Worker {
+            jobs,
+            thread: thread.thread().id(),
+        })
+    }
+
+    fn stopped() -> Error {
+        Error::InvalidOperation("The thread running the program stopped".to_string())
+    }
+}
+
+impl NativeModule {
+    pub fn new() -> NativeModule {
+        NativeModule::default()
+    }
+
+    pub fn bind_function<F>(&mut self, name: &str, function: F)
+    where
+        F: Fn(&Arc<Env>, &Value) -> Result<Value, Error> + Send + Sync + 'static,
+    {
+        self.bind_function_with_shape(name, Shape::Singleton, function);
+    }
+
+    // Binds a native that takes arguments of `shape`, like a lambda with
+    // positional or named parameters.
+    pub fn bind_function_with_shape<F>(&mut self, name: &str, shape: Shape, function: F)
+    where
+        F: Fn(&Arc<Env>, &Value) -> Result<Value, Error> + Send + Sync + 'static,
+    {
+        self.bind_value(name, Function::native(name, shape, function));
+    }
+
+    // Binds a function with a typed Rust signature; see `TypedFunction`.
+    pub fn bind_typed_function<Args, F: TypedFunction<Args>>(&mut self, name: &str, function: F) {
+        self.bind_value(name, Function::typed(name, function));
+    }
+
+    pub fn bind_special_form<F>(&mut self, name: &str, special_form: F)
+    where
+        F: Fn(&Arc<Env>, &Object, &Value) -> Result<Value, Error> + Send + Sync + 'static,
+    {
+        self.bind_value(name, Function::native_special_form(special_form));
+    }
+
+    pub fn bind_value(&mut self, name: &str, value: Value) {
+        self.exports.insert(Str::key(name), value);
+    }
+}
@@ -0,0 +1,184 @@
+use super::*;
+
+const VOID_ELEMENTS: [&str; 13] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+const RAW_TEXT_ELEMENTS: [&str; 2] = ["script", "style"];
+
+// Renders a structured description of a document as HTML5.
+//
+// A description is one of:
+//  * A string, number, or bool, which is rendered as escaped text.
+//  * `null`, which renders nothing.
+//  * An array, whose entries are rendered one after another.
+//  * `{ raw: "<b>markup</b>" }`, which is emitted verbatim.
+//  * `{ tag: div, attributes: { ... }, children: [...], text: "..." }`, which
+//    renders an element. Attributes set to `true` are rendered bare and those
+//    set to `false` or `null` are omitted. `text` is rendered after
+//    `children`. The content of `<script>` and `<style>` isn't escaped by
+//    browsers, so it has to be given as `raw`.
+pub fn render(value: &Value) -> Result<String, Error> {
+    let mut output = String::new();
+    render_node(value, &mut output)?;
+    Ok(output)
+}
+
+fn render_node(value: &Value, output: &mut String) -> Result<(), Error> {
+    match value {
+        Value::Null => {}
+        Value::Bool(_) | Value::Number(_) => output.push_str(&serialize(value)?),
+        Value::String(text) => escape_text(text, output),
+        Value::Array(values) => {
+            for value in values.iter() {
+                render_node(value, output)?;
+            }
+        }
+        Value::Object(object) => {
+            if let Some(raw) = object.get("raw") {
+                output.push_str(Value::as_str(raw)?);
+            } else {
+                render_element(object, output)?;
+            }
+        }
+        Value::Function(_) | Value::Opaque(_) => {
+            return Err(Error::invalid_type("HTML node", value))
+        }
+    }
+    Ok(())
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == ':' || c == '.')
+}
+
+fn render_element(object: &Object, output: &mut String) -> Result<(), Error> {
+    for key in object.keys() {
+        if !matches!(key.as_str(), "tag" | "attributes" | "children" | "text") {
+            return Err(Error::InvalidHtml(format!("Unknown element key: {}", key)));
+        }
+    }
+    let tag = Value::as_str(get_key(object, "tag")?)?;
+    if !is_valid_name(tag) {
+        return Err(Error::InvalidHtml(format!("Invalid tag name: {}", tag)));
+    }
+    let tag = tag.to_ascii_lowercase();
+    output.push('<');
+    output.push_str(&tag);
+    if let Some(attributes) = object.get("attributes") {
+        render_attributes(Value::as_object(attributes)?, output)?;
+    }
+    output.push('>');
+
+    let children = object.get("children").unwrap_or(&Value::Null);
+    let text = object.get("text").unwrap_or(&Value::Null);
+    if VOID_ELEMENTS.contains(&tag.as_str()) {
+        if *children != Value::Null || *text != Value::Null {
+            return Err(Error::InvalidHtml(format!(
+                "<{}> is a void element and cannot have content",
+                tag
+            )));
+        }
+        return Ok(());
+    }
+    if RAW_TEXT_ELEMENTS.contains(&tag.as_str()) {
+        render_raw_text(&tag, children, output)?;
+        render_raw_text(&tag, text, output)?;
+    } else {
+        render_node(children, output)?;
+        render_node(text, output)?;
+    }
+    output.push_str("</");
+    output.push_str(&tag);
+    output.push('>');
+    Ok(())
+}
+
+fn render_attributes(attributes: &Object, output: &mut String) -> Result<(), Error> {
+    for name in attributes.keys() {
+        if !is_valid_name(name) {
+            return Err(Error::InvalidHtml(format!(
+                "Invalid attribute name: {}",
+                name
+            )));
+        }
+        let value = match &attributes[name.as_str()] {
+            Value::Null | Value::Bool(false) => continue,
+            Value::Bool(true) => {
+                output.push(' ');
+                output.push_str(name);
+                continue;
+            }
+            Value::String(value) => value.to_string(),
+            value @ Value::Number(_) => serialize(value)?,
+            value => return Err(Error::invalid_type("attribute value", value)),
+        };
+        output.push(' ');
+        output.push_str(name);
+        output.push_str("=\"");
+        escape_attribute(&value, output);
+        output.push('"');
+    }
+    Ok(())
+}
+
+// The contents of <script> and <style> are not parsed for entities, so text
+// can't be escaped there. Only raw content is accepted, so that a plain string
+// can't inject code, and it must not be able to close the element early.
+fn render_raw_text(tag: &str, value: &Value, output: &mut String) -> Result<(), Error> {
+    let text = match value {
+        Value::Null => return Ok(()),
+        Value::Array(values) => {
+            for value in values.iter() {
+                render_raw_text(tag, value, output)?;
+            }
+            return Ok(());
+        }
+        Value::Object(object) if object.contains_key("raw") => {
+            Value::as_str(get_key(object, "raw")?)?
+        }
+        _ => {
+            return Err(Error::InvalidHtml(format!(
+                "Content of <{}> must be given as {{ raw: ... }}",
+                tag
+            )))
+        }
+    };
+    if text.to_ascii_lowercase().contains(&format!("</{}", tag)) {
+        return Err(Error::InvalidHtml(format!(
+            "Content of <{}> cannot contain </{}",
+            tag, tag
+        )));
+    }
+    output.push_str(text);
+    Ok(())
+}
+
+fn escape_text(text: &str, output: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            _ => output.push(c),
+        }
+    }
+}
+
+fn escape_attribute(text: &str, output: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '"' => output.push_str("&quot;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            _ => output.push(c),
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use super::*;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Bounds on what a run may use, for evaluating programs that aren't trusted.
+// Each limit is off when `None`, and exceeding one fails the run with an error
+// of its own.
+//
+// Deep recursion would overflow the stack before a run runs out of steps or
+// time, so when any limit is set the call depth is limited too, by
+// `DEFAULT_MAX_CALL_DEPTH` unless `max_call_depth` is given, and a `Context`
+// runs its programs on a thread of its own whose stack holds calls that deep.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Limits {
+    // The number of nodes evaluated.
+    pub max_steps: Option<u64>,
+    // The number of lambda calls in progress at once.
+    pub max_call_depth: Option<usize>,
+    // The length of the arrays, objects, and strings a run builds, in elements,
+    // entries, and bytes.
+    pub max_collection_size: Option<usize>,
+    // How long a run may take.
+    pub timeout: Option<Duration>,
+}
+
+// The call depth of a run with limits that don't give one.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 10_000;
+
+// The stack a run needs for each call in progress, and for the rest of the
+// run. Unoptimized builds use a lot more stack per call.
+const STACK_PER_CALL: usize = if cfg!(debug_assertions) {
+    64 * 1024
+} else {
+    16 * 1024
+};
+const STACK_BASE: usize = 4 * 1024 * 1024;
+
+impl Limits {
+    pub fn is_unlimited(&self) -> bool {
+        *self == Limits::default()
+    }
+}
+
+// How often, in steps, the deadline is checked, since reading the clock costs
+// more than a step.
+const DEADLINE_INTERVAL: u64 = 1024;
+
+// What a run has used of its `Limits`, shared by every `Env` of the run.
+#[derive(Debug)]
+pub struct Budget {
+    limits: Limits,
+    steps: AtomicU64,
+    depth: AtomicUsize,
+    deadline: Mutex<Option<Instant>>,
+}
+
+// Leaves a call when dropped.
+pub struct CallGuard<'a> {
+    budget: &'a Budget,
+}
+
+impl Budget {
+    pub fn new(mut limits: Limits) -> Budget {
+        limits.max_call_depth = limits.max_call_depth.or(Some(DEFAULT_MAX_CALL_DEPTH));
+        let budget = Budget {
+            limits,
+            steps: AtomicU64::new(0),
+            depth: AtomicUsize::new(0),
+            deadline: Mutex::new(None),
+        };
+        budget.start();
+        budget
+    }
+
+    // Starts a new run, with nothing used yet.
+    pub fn start(&self) {
+        self.steps.store(0, Ordering::Relaxed);
+        let deadline = self.limits.timeout.map(|timeout| Instant::now() + timeout);
+        *self.deadline.lock().unwrap() = deadline;
+    }
+
+    // The size of the stack to run a program on, which holds the deepest calls
+    // the limits allow.
+    pub fn stack_size(&self) -> usize {
+        let depth = self.limits.max_call_depth.unwrap_or(DEFAULT_MAX_CALL_DEPTH);
+        depth
+            .saturating_mul(STACK_PER_CALL)
+            .saturating_add(STACK_BASE)
+    }
+
+    pub fn step(&self) -> Result<(), Error> {
+        let steps = self.steps.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(max_steps) = self.limits.max_steps {
+            if steps > max_steps {
+                return Err(Error::StepLimitExceeded(max_steps));
+            }
+        }
+        if steps.is_multiple_of(DEADLINE_INTERVAL) {
+            self.check_deadline()?;
+        }
+        Ok(())
+    }
+
+    // Fails if the run is past its deadline. Besides every so many steps, it
+    // is checked around each native call and while splicing, which may take
+    // long without taking steps.
+    pub fn check_deadline(&self) -> Result<(), Error> {
+        match *self.deadline.lock().unwrap() {
+            Some(deadline) if Instant::now() >= deadline => Err(Error::DeadlineExceeded),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn enter_call(&self) -> Result<CallGuard<'_>, Error> {
+        let depth = self.depth.fetch_add(1, Ordering::Relaxed) + 1;
+        let guard = CallGuard { budget: self };
+        match self.limits.max_call_depth {
+            Some(max_call_depth) if depth > max_call_depth => {
+                Err(Error::CallDepthExceeded(max_call_depth))
+            }
+            _ => Ok(guard),
+        }
+    }
+
+    // Fails if a collection of `size` elements or entries, or a string of
+    // `size` bytes, is too large. It is checked before building one, where
+    // the size is known in advance.
+    pub fn check_length(&self, size: usize) -> Result<(), Error> {
+        match self.limits.max_collection_size {
+            Some(max_collection_size) if size > max_collection_size => {
+                Err(Error::CollectionSizeExceeded(max_collection_size))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Like `check_length`, for a value already built, such as the result of a
+    // native.
+    pub fn check_size(&self, value: &Value) -> Result<(), Error> {
+        let size = match value {
+            Value::String(string) => string.len(),
+            Value::Array(values) => values.len(),
+            Value::Object(object) => object.len(),
+            _ => 0,
+        };
+        self.check_length(size)
+    }
+}
+
+impl Drop for CallGuard<'_> {
+    fn drop(&mut self) {
+        self.budget.depth.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -4,49 +4,127 @@ use serde_yaml;
 use std::sync::Arc;
 
 mod builtins;
+mod check;
+mod compile;
+mod convert;
+mod data;
+mod engine;
+mod html;
+mod limits;
+mod module;
+mod native;
+mod opaque;
+mod pattern;
+mod query;
+mod schema;
+mod stdlib;
+mod string;
+mod types;
+mod walk;
+
+pub use check::{check, Problem};
+#[doc(hidden)]
+pub use compile::compiled_forms;
+use compile::{Form, LambdaCode};
+pub use convert::{from_value, to_value};
+pub use data::Format;
+pub use engine::{Context, Engine, NativeModule};
+use limits::Budget;
+pub use limits::{Limits, DEFAULT_MAX_CALL_DEPTH};
+pub use module::{search_path_from_env, Host, Location, Modules};
+use native::Native;
+pub use native::{FromValue, IntoValue, Named, Shape, TypedFunction};
+pub use opaque::Opaque;
+pub use pattern::Pattern;
+pub use query::Query;
+pub use schema::{validate, ValidationError};
+pub use stdlib::STD_VERSION;
+pub use string::Str;
+pub use types::Type;
+use walk::Walker;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Error {
     AmbiguousOperation(Vec<Op>),
     ArgumentCountMismatch(usize, usize),
+    CallDepthExceeded(usize),
+    CircularImport(Vec<String>),
+    CollectionSizeExceeded(usize),
+    Conversion(String),
+    DeadlineExceeded,
+    InvalidArgument(String, String),
+    InvalidData(String),
+    InvalidHtml(String),
     InvalidIndex(usize, usize),
-    InvalidNumber(Number),
+    InvalidNumber(String),
     InvalidOperation(String),
+    InvalidPattern(String),
+    InvalidProgram(String),
+    InvalidQuery(String),
+    InvalidSchema(String),
     InvalidType(String),
     IO,
+    MissingExport(String, String),
     MissingNamedArgument(Arc<String>),
     MissingOperation,
+    MissingPath(String),
+    ModuleNotFound(String, Vec<String>),
+    NameCollision(String),
+    NoMatchingCase,
     Parse,
+    PatternMismatch(String),
     Serialization,
+    StepLimitExceeded(u64),
     UndefinedSymbol(String),
     UnknownKey(String),
 }
 
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl std::error::Error for Error {}
+
 impl Error {
     fn invalid_type(expected: &str, actual: &Value) -> Error {
-        Error::InvalidType(format!("Expected {}, got {}", expected, actual.type_of()))
+        Error::InvalidType(format!(
+            "Expected {}, got {}",
+            expected,
+            actual.type_description()
+        ))
     }
 }
 
-pub type ObjectMap = Map<String, Value>;
+// Objects keep their keys in insertion order, which for parsed values is the
+// order they appear in the source, so serializing is deterministic.
+pub type ObjectMap = indexmap::IndexMap<Str, Value>;
 pub type Object = Arc<ObjectMap>;
+
+// The variables of an `Env`. They are kept in insertion order so compiled code
+// can address them by position.
+pub type Variables = indexmap::IndexMap<String, Value>;
 pub type Map<K, V> = std::collections::HashMap<K, V>;
 pub type Number = serde_json::Number;
-pub type NativeFunction = fn(&Arc<Env>, &Value) -> Result<Value, Error>;
-pub type NativeSpecialForm = fn(&Arc<Env>, &Object, &Value) -> Result<Value, Error>;
-
-// TODO: Use a smarter handle than Arc to store null, bool, number, and string
-// without needing a heap allocation.
-
+// Natives may be closures that capture state of the host, so they are shared
+// between threads along with the values that hold them.
+pub type NativeFunction = Arc<dyn Fn(&Arc<Env>, &Value) -> Result<Value, Error> + Send + Sync>;
+pub type NativeSpecialForm =
+    Arc<dyn Fn(&Arc<Env>, &Object, &Value) -> Result<Value, Error> + Send + Sync>;
+
+// Null, bool, and number are stored without a heap allocation, as are strings
+// short enough to fit in a `Str`.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Value {
     Null,
     Bool(bool),
     Number(Number),
-    String(Arc<String>),
+    String(Str),
     Array(Arc<Vec<Value>>),
     Object(Object),
     Function(Arc<Function>),
+    Opaque(Arc<Opaque>),
 }
 
 impl Value {
@@ -54,6 +132,13 @@ impl Value {
         Value::Null
     }
 
+    // The names `type_of` returns, which patterns and queries match types by.
+    pub const TYPE_NAMES: [&'static str; 8] = [
+        "null", "bool", "number", "string", "array", "object", "function", "opaque",
+    ];
+
+    // The type of the value. Every opaque handle is an `opaque`, whatever the
+    // host calls it, so that a handle never passes for another type.
     pub fn type_of(&self) -> &str {
         match self {
             Value::Null => "null",
@@ -63,6 +148,17 @@ impl Value {
             Value::Array(_) => "array",
             Value::Object(_) => "object",
             Value::Function(_) => "function",
+            Value::Opaque(_) => "opaque",
+        }
+    }
+
+    // Like `type_of`, with the name the host gave an opaque handle, for
+    // messages. It is written like the type that matches it, as in
+    // `opaque<regex>`.
+    pub fn type_description(&self) -> String {
+        match self {
+            Value::Opaque(opaque) => format!("opaque<{}>", opaque.type_name()),
+            value => value.type_of().to_string(),
         }
     }
 
@@ -89,10 +185,11 @@ impl Value {
 
     pub fn as_f64(value: &Value) -> Result<f64, Error> {
         let n = Self::as_number(value)?;
-        n.as_f64().ok_or_else(|| Error::InvalidNumber(n))
+        n.as_f64()
+            .ok_or_else(|| Error::InvalidNumber(n.to_string()))
     }
 
-    pub fn as_string(value: &Value) -> Result<&Arc<String>, Error> {
+    pub fn as_string(value: &Value) -> Result<&Str, Error> {
         match value {
             Value::String(value) => Ok(value),
             _ => Err(Error::invalid_type("string", value)),
@@ -113,6 +210,13 @@ impl Value {
         }
     }
 
+    pub fn as_opaque(value: &Value) -> Result<&Arc<Opaque>, Error> {
+        match value {
+            Value::Opaque(value) => Ok(value),
+            _ => Err(Error::invalid_type("opaque handle", value)),
+        }
+    }
+
     pub fn as_object(value: &Value) -> Result<&Object, Error> {
         match value {
             Value::Object(value) => Ok(value),
@@ -129,9 +233,10 @@ impl Value {
 }
 
 enum FunctionBody {
-    Native(NativeFunction),
+    Native(Native),
     Lambda(Lambda),
     NativeSpecialForm(NativeSpecialForm),
+    SpecialForm(Form),
 }
 
 impl std::fmt::Debug for FunctionBody {
@@ -156,111 +261,193 @@ pub struct Function {
 }
 
 impl Function {
+    // Returns a function value that calls `function` with its evaluated
+    // arguments, once they are checked against `shape`. `name` is used in
+    // errors.
+    pub fn native<F>(name: &str, shape: Shape, function: F) -> Value
+    where
+        F: Fn(&Arc<Env>, &Value) -> Result<Value, Error> + Send + Sync + 'static,
+    {
+        Value::Function(Arc::new(Function {
+            body: FunctionBody::Native(Native::new(name, shape, Arc::new(function))),
+        }))
+    }
+
+    // Returns a special form that calls `special_form` with the operation
+    // object and its unevaluated arguments.
+    pub fn native_special_form<F>(special_form: F) -> Value
+    where
+        F: Fn(&Arc<Env>, &Object, &Value) -> Result<Value, Error> + Send + Sync + 'static,
+    {
+        Value::Function(Arc::new(Function {
+            body: FunctionBody::NativeSpecialForm(Arc::new(special_form)),
+        }))
+    }
+
     fn eval(&self, env: &Arc<Env>, object: &Object, args: &Value) -> Result<Value, Error> {
         match &self.body {
-            FunctionBody::Native(native) => {
-                // TODO: This isn't quite right. We need to be able to know whether
-                // the native function takes an object, an array, or a singleton value.
-                let args = eval(env, args)?;
-                native(env, &args)
-            }
+            FunctionBody::Native(native) => native.eval(env, args),
             FunctionBody::Lambda(lambda) => lambda.eval(env, args),
             FunctionBody::NativeSpecialForm(native) => native(env, object, args),
+            FunctionBody::SpecialForm(form) => compile::run_form(*form, env, object, args),
         }
     }
 
-    pub fn call(&self, env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
+    // How the function takes its arguments, or `None` for a special form,
+    // which takes them unevaluated.
+    pub fn shape(&self) -> Option<Shape> {
         match &self.body {
-            FunctionBody::Native(native) => native(env, args),
-            FunctionBody::Lambda(lambda) => lambda.call(args),
-            FunctionBody::NativeSpecialForm(_) => Err(Error::InvalidOperation(
-                "Cannot call special form".to_string(),
-            )),
+            FunctionBody::Native(native) => Some(native.shape().clone()),
+            FunctionBody::Lambda(lambda) => Some(lambda.code.formals.shape()),
+            FunctionBody::NativeSpecialForm(_) | FunctionBody::SpecialForm(_) => None,
         }
     }
-}
-
-fn from_serde(value: serde_json::Value) -> Value {
-    match value {
-        serde_json::Value::Null => Value::Null,
-        serde_json::Value::Bool(value) => Value::Bool(value),
-        serde_json::Value::Number(value) => Value::Number(value),
-        serde_json::Value::String(value) => Value::String(Arc::new(value)),
-        serde_json::Value::Array(values) => Value::Array(Arc::new(
-            values.into_iter().map(|value| from_serde(value)).collect(),
-        )),
-        serde_json::Value::Object(value) => Value::Object(Arc::new(Map::from_iter(
-            value
-                .into_iter()
-                .map(|(key, value)| (key, from_serde(value))),
-        ))),
-    }
-}
 
-fn to_serde(value: &Value) -> serde_json::Value {
-    match value {
-        Value::Null => serde_json::Value::Null,
-        Value::Bool(value) => serde_json::Value::Bool(value.clone()),
-        Value::Number(value) => serde_json::Value::Number(value.clone()),
-        Value::String(value) => serde_json::Value::String(value.to_string()),
-        Value::Array(values) => {
-            serde_json::Value::Array(values.iter().map(|value| to_serde(&value)).collect())
+    pub fn call(&self, env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
+        match &self.body {
+            FunctionBody::Native(native) => native.call(env, args),
+            FunctionBody::Lambda(lambda) => lambda.call(args),
+            FunctionBody::NativeSpecialForm(_) | FunctionBody::SpecialForm(_) => Err(
+                Error::InvalidOperation("Cannot call special form".to_string()),
+            ),
         }
-        Value::Object(value) => serde_json::Value::Object(serde_json::map::Map::from_iter(
-            value
-                .iter()
-                .map(|(key, value)| (key.clone(), to_serde(&value))),
-        )),
-        Value::Function(_) => serde_json::Value::String("#function".to_string()),
     }
 }
 
 pub fn parse(json: &str) -> Result<Value, Error> {
-    let value: serde_json::Value = serde_yaml::from_str(json).map_err(|_| Error::Parse)?;
-    Ok(from_serde(value))
+    serde_yaml::from_str(json).map_err(|_| Error::Parse)
 }
 
 pub fn serialize(value: &Value) -> Result<String, Error> {
-    // TODO: Avoid cloning the entire value just to serialize it.
-    let value: serde_json::Value = to_serde(value);
-    serde_json::to_string(&value).map_err(|_| Error::Serialization)
+    serde_json::to_string(value).map_err(|_| Error::Serialization)
+}
+
+// Serializes `value` with the keys of every object sorted, so that equal values
+// always produce the same bytes regardless of how they were built.
+pub fn serialize_sorted(value: &Value) -> Result<String, Error> {
+    serde_json::to_string(&convert::Sorted(value)).map_err(|_| Error::Serialization)
 }
 
 pub const FILE_SYMBOL: &str = "__file__";
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug)]
 pub struct Env {
-    pub variables: ObjectMap,
+    pub variables: Variables,
     pub parent: Option<Arc<Env>>,
+    // Only set on a root `Env`; see `modules`.
+    modules: Option<Arc<Modules>>,
+    // The budget of the run, if it has limits, which every `Env` of the run
+    // shares.
+    budget: Option<Arc<Budget>>,
+    // Whether the variables are provided to the program, like the builtins and
+    // the prelude, rather than bound by it. Imports may shadow them.
+    provided: bool,
 }
 
 impl Env {
     pub fn builtin(path: String) -> Arc<Env> {
+        let modules = Modules::new(&path, Vec::new());
+        Env::builtin_with_modules(path, Arc::new(modules))
+    }
+
+    // Like `builtin`, sharing the modules already loaded by `modules`.
+    pub fn builtin_with_modules(path: String, modules: Arc<Modules>) -> Arc<Env> {
         let mut env = Env {
-            variables: ObjectMap::new(),
+            variables: Variables::new(),
             parent: None,
+            modules: None,
+            budget: modules.budget().cloned(),
+            provided: true,
         };
         env.bind_string(FILE_SYMBOL, path);
-        env.bind_native_function("deserialize", builtins::deserialize);
-        env.bind_native_function("eval", eval);
-        env.bind_native_function("map", builtins::map);
-        env.bind_native_function("print", builtins::print);
-        env.bind_native_function("println", builtins::println);
-        env.bind_native_function("serialize", builtins::serialize);
-        env.bind_native_function("eq", builtins::eq);
-        env.bind_native_function("+", builtins::plus);
-        env.bind_native_special_form("$", builtins::lookup);
-        env.bind_native_special_form("export", builtins::export);
-        env.bind_native_special_form("import", builtins::import);
-        env.bind_native_special_form("lambda", builtins::lambda);
-        env.bind_native_special_form("let", builtins::nonrecursive_let);
-        env.bind_native_special_form("quote", builtins::quote);
-        env.bind_native_special_form("if", builtins::if_func);
+        env.bind_typed_function("deserialize", builtins::deserialize);
+        env.bind_native_function("eval", Shape::Singleton, eval);
+        env.bind_typed_function("html", builtins::html);
+        env.bind_native_function("map", Shape::Positional { min: 2, max: 2 }, builtins::map);
+        env.bind_native_function(
+            "postwalk",
+            Shape::Positional { min: 2, max: 3 },
+            builtins::postwalk,
+        );
+        env.bind_native_function(
+            "prewalk",
+            Shape::Positional { min: 2, max: 3 },
+            builtins::prewalk,
+        );
+        env.bind_typed_function("print", builtins::print);
+        env.bind_typed_function("query", builtins::query);
+        env.bind_native_function(
+            "replace_map",
+            Shape::Positional { min: 3, max: 4 },
+            builtins::replace_map,
+        );
+        env.bind_typed_function("println", builtins::println);
+        env.bind_typed_function("serialize", builtins::serialize);
+        env.bind_typed_function("validate", builtins::validate);
+        env.bind_native_function("walk", Shape::Positional { min: 3, max: 4 }, builtins::walk);
+        env.bind_typed_function("eq", builtins::eq);
+        env.bind_typed_function("+", builtins::plus);
+        env.bind_special_form("$", Form::Lookup);
+        env.bind_special_form("export", Form::Export);
+        env.bind_special_form("import", Form::Import);
+        env.bind_special_form("lambda", Form::Lambda);
+        env.bind_special_form("let", Form::Let);
+        env.bind_special_form("quote", Form::Quote);
+        env.bind_special_form("quasiquote", Form::Quasiquote);
+        env.bind_special_form("if", Form::If);
+        env.bind_special_form("case", Form::Case);
+        env.bind_special_form("compile", Form::Compile);
+        let bindings = modules.bindings().iter();
+        env.variables
+            .extend(bindings.map(|(name, value)| (name.clone(), value.clone())));
+        env.modules = Some(modules);
         Arc::new(env)
     }
 
-    pub fn new(variables: ObjectMap, parent: Option<Arc<Env>>) -> Arc<Env> {
-        Arc::new(Env { variables, parent })
+    pub fn new(variables: Variables, parent: Option<Arc<Env>>) -> Arc<Env> {
+        let budget = parent.as_ref().and_then(|parent| parent.budget.clone());
+        Arc::new(Env {
+            variables,
+            parent,
+            modules: None,
+            budget,
+            provided: false,
+        })
+    }
+
+    // Like `new`, for variables provided to the program rather than bound by
+    // it.
+    fn provided(variables: Variables, parent: Arc<Env>) -> Arc<Env> {
+        Arc::new(Env {
+            variables,
+            budget: parent.budget.clone(),
+            parent: Some(parent),
+            modules: None,
+            provided: true,
+        })
+    }
+
+    // Returns a child of this `Env` with the exports of the standard prelude
+    // bound, for running a program with the prelude enabled.
+    pub fn with_prelude(self: &Arc<Env>) -> Result<Arc<Env>, Error> {
+        let file = Value::as_str(self.lookup(FILE_SYMBOL)?)?;
+        let location = Location::Std(stdlib::PRELUDE);
+        let exports =
+            self.modules()
+                .load(std::path::Path::new(file), &location, Format::Program)?;
+        let variables = Value::as_object(&exports)?
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+        Ok(Env::provided(variables, self.clone()))
+    }
+
+    // Returns the modules loaded during the run this `Env` belongs to.
+    pub fn modules(&self) -> Arc<Modules> {
+        match &self.parent {
+            Some(parent) => parent.modules(),
+            None => self.modules.clone().unwrap_or_default(),
+        }
     }
 
     pub fn lookup(&self, name: &str) -> Result<&Value, Error> {
@@ -273,25 +460,77 @@ impl Env {
         }
     }
 
+    // Fails if the run is past its deadline, for natives that may take long to
+    // check every so often.
+    pub fn check_deadline(&self) -> Result<(), Error> {
+        match &self.budget {
+            Some(budget) => budget.check_deadline(),
+            None => Ok(()),
+        }
+    }
+
+    // Fails if a collection of `size` elements or entries, or a string of
+    // `size` bytes, is larger than the run may build, for natives to check
+    // before building one.
+    pub fn check_length(&self, size: usize) -> Result<(), Error> {
+        match &self.budget {
+            Some(budget) => budget.check_length(size),
+            None => Ok(()),
+        }
+    }
+
+    // Whether `name` is bound by the program, rather than only provided to it.
+    pub fn binds(&self, name: &str) -> bool {
+        (!self.provided && self.variables.contains_key(name))
+            || self
+                .parent
+                .as_ref()
+                .is_some_and(|parent| parent.binds(name))
+    }
+
+    // Returns the `Env` `depth` levels up the chain, which compiled code
+    // guarantees exists.
+    fn ancestor(&self, depth: usize) -> &Env {
+        let mut env = self;
+        for _ in 0..depth {
+            env = env.parent.as_deref().unwrap();
+        }
+        env
+    }
+
     pub fn bind_string(&mut self, name: &str, string: String) {
         self.variables
-            .insert(name.to_string(), Value::String(Arc::new(string)));
+            .insert(name.to_string(), Value::String(Str::from(string)));
+    }
+
+    pub fn bind_native_function<F>(&mut self, name: &str, shape: Shape, function: F)
+    where
+        F: Fn(&Arc<Env>, &Value) -> Result<Value, Error> + Send + Sync + 'static,
+    {
+        self.variables
+            .insert(name.to_string(), Function::native(name, shape, function));
     }
 
-    pub fn bind_native_function(&mut self, name: &str, function: NativeFunction) {
+    pub fn bind_typed_function<Args, F: TypedFunction<Args>>(&mut self, name: &str, function: F) {
+        self.variables
+            .insert(name.to_string(), Function::typed(name, function));
+    }
+
+    pub fn bind_native_special_form<F>(&mut self, name: &str, special_form: F)
+    where
+        F: Fn(&Arc<Env>, &Object, &Value) -> Result<Value, Error> + Send + Sync + 'static,
+    {
         self.variables.insert(
             name.to_string(),
-            Value::Function(Arc::new(Function {
-                body: FunctionBody::Native(function),
-            })),
+            Function::native_special_form(special_form),
         );
     }
 
-    pub fn bind_native_special_form(&mut self, name: &str, special_form: NativeSpecialForm) {
+    fn bind_special_form(&mut self, name: &str, form: Form) {
         self.variables.insert(
             name.to_string(),
             Value::Function(Arc::new(Function {
-                body: FunctionBody::NativeSpecialForm(special_form),
+                body: FunctionBody::SpecialForm(form),
             })),
         );
     }
@@ -300,24 +539,50 @@ impl Env {
 #[derive(Debug, PartialEq, Eq)]
 pub enum Formals {
     Singleton(Arc<String>),
-    Positional(Vec<Arc<String>>),
-    Named(Vec<Arc<String>>),
+    Positional(Pattern),
+    Named(Pattern),
 }
 
-#[derive(Debug, PartialEq, Eq)]
+impl Formals {
+    pub fn names(&self) -> Vec<Arc<String>> {
+        match self {
+            Formals::Singleton(name) => vec![name.clone()],
+            Formals::Positional(pattern) | Formals::Named(pattern) => pattern.names(),
+        }
+    }
+
+    pub fn shape(&self) -> Shape {
+        match self {
+            Formals::Positional(Pattern::Array { items, rest }) => Shape::Positional {
+                min: items.len(),
+                max: if rest.is_some() {
+                    usize::MAX
+                } else {
+                    items.len()
+                },
+            },
+            Formals::Named(Pattern::Object { fields, .. }) => Shape::Named {
+                required: fields.iter().map(|(name, _)| Str::key(name)).collect(),
+                optional: Vec::new(),
+            },
+            _ => Shape::Singleton,
+        }
+    }
+}
+
+#[derive(Debug)]
 struct Lambda {
     env: Arc<Env>,
-    formals: Formals,
-    body: Value,
+    code: Arc<LambdaCode>,
 }
 
 impl Lambda {
     fn eval(&self, env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
-        let args = match &self.formals {
+        let args = match &self.code.formals {
             Formals::Singleton(_) => eval(env, args)?,
             Formals::Positional(_) => {
                 let array = Value::as_array(args)?;
-                Value::Array(eval_array(env, &array)?)
+                Value::Array(eval_array(env, array)?)
             }
             Formals::Named(_) => {
                 let object = Value::as_object(args)?;
@@ -328,37 +593,59 @@ impl Lambda {
     }
 
     fn call(&self, args: &Value) -> Result<Value, Error> {
-        let mut variables = ObjectMap::new();
-        match &self.formals {
+        let _call = match &self.env.budget {
+            Some(budget) => Some(budget.enter_call()?),
+            None => None,
+        };
+        let mut variables = Variables::new();
+        match &self.code.formals {
             Formals::Singleton(name) => {
                 variables.insert(name.to_string(), args.clone());
             }
-            Formals::Positional(names) => {
+            Formals::Positional(pattern) => {
                 let values = Value::as_array(args)?;
-                if names.len() != values.len() {
-                    return Err(Error::ArgumentCountMismatch(names.len(), values.len()));
-                }
-                for (name, actual) in names.iter().zip(values.iter()) {
-                    variables.insert(name.to_string(), actual.clone());
+                if let Pattern::Array { items, rest: None } = pattern {
+                    if items.len() != values.len() {
+                        return Err(Error::ArgumentCountMismatch(items.len(), values.len()));
+                    }
                 }
+                pattern.destructure(args, &mut variables)?;
             }
-            Formals::Named(names) => {
+            Formals::Named(pattern) => {
                 let values = Value::as_object(args)?;
-                for name in names.iter() {
-                    let actual = values
-                        .get(name.as_ref())
-                        .ok_or_else(|| Error::MissingNamedArgument(name.clone()))?
-                        .clone();
-                    variables.insert(name.to_string(), actual);
+                if let Pattern::Object { fields, .. } = pattern {
+                    for (name, _) in fields.iter() {
+                        if !values.contains_key(name.as_str()) {
+                            return Err(Error::MissingNamedArgument(Arc::new(name.clone())));
+                        }
+                    }
                 }
+                pattern.destructure(args, &mut variables)?;
             }
         };
+        for (name, parameter_type) in self.code.parameter_types.iter() {
+            if let Some(value) = variables.get(name.as_str()) {
+                if let Some(problem) = parameter_type.check(value, name) {
+                    return Err(Error::InvalidType(format!(
+                        "Parameter `{}`: {}",
+                        name, problem
+                    )));
+                }
+            }
+        }
         let env = Env::new(variables, Some(self.env.clone()));
-        eval(&env, &self.body)
+        let result = self.code.body.run(&env)?;
+        if let Some(result_type) = &self.code.result_type {
+            if let Some(problem) = result_type.check(&result, "result") {
+                return Err(Error::InvalidType(format!("Result: {}", problem)));
+            }
+        }
+        Ok(result)
     }
 }
 
-pub fn eval_array(env: &Arc<Env>, array: &Vec<Value>) -> Result<Arc<Vec<Value>>, Error> {
+pub fn eval_array(env: &Arc<Env>, array: &[Value]) -> Result<Arc<Vec<Value>>, Error> {
+    env.check_length(array.len())?;
     Ok(Arc::new(
         array
             .iter()
@@ -368,6 +655,7 @@ pub fn eval_array(env: &Arc<Env>, array: &Vec<Value>) -> Result<Arc<Vec<Value>>,
 }
 
 pub fn eval_object(env: &Arc<Env>, object: &Object) -> Result<Object, Error> {
+    env.check_length(object.len())?;
     Ok(Arc::new(
         object
             .iter()
@@ -380,17 +668,7 @@ pub fn eval_object(env: &Arc<Env>, object: &Object) -> Result<Object, Error> {
 }
 
 pub fn eval(env: &Arc<Env>, value: &Value) -> Result<Value, Error> {
-    Ok(match value {
-        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) | Value::Function(_) => {
-            value.clone()
-        }
-        Value::Array(values) => Value::Array(eval_array(env, values)?),
-        Value::Object(object) => {
-            let op = get_op(object)?;
-            let func = Value::as_function(env.lookup(&op.name)?)?;
-            func.eval(env, object, &op.args)?
-        }
-    })
+    compile::compile(env, value).run(env)
 }
 
 pub fn get_key<'a>(object: &'a Object, key: &str) -> Result<&'a Value, Error> {
@@ -399,10 +677,10 @@ pub fn get_key<'a>(object: &'a Object, key: &str) -> Result<&'a Value, Error> {
         .ok_or_else(|| Error::UnknownKey(key.to_string()))
 }
 
-pub fn get_index(array: &Vec<Value>, index: usize) -> Result<&Value, Error> {
+pub fn get_index(array: &[Value], index: usize) -> Result<&Value, Error> {
     array
         .get(index)
-        .ok_or_else(|| Error::InvalidIndex(index, array.len()))
+        .ok_or(Error::InvalidIndex(index, array.len()))
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -411,13 +689,24 @@ pub struct Op {
     args: Value,
 }
 
+// Keys that start with `+` are arguments to an operation rather than
+// operations themselves, except for `+` itself.
+fn is_op_key(key: &str) -> bool {
+    key.len() == 1 || !key.starts_with('+')
+}
+
+// Appends `segment` to a JSON pointer, escaping it as needed.
+fn pointer_child(path: &str, segment: &str) -> String {
+    format!("{}/{}", path, segment.replace('~', "~0").replace('/', "~1"))
+}
+
 fn get_op(object: &Object) -> Result<Op, Error> {
     let ops: Vec<Op> = object
         .iter()
         .filter_map(|(key, value)| {
-            if key.len() == 1 || !key.starts_with("+") {
+            if is_op_key(key) {
                 Some(Op {
-                    name: key.clone(),
+                    name: key.to_string(),
                     args: value.clone(),
                 })
             } else {
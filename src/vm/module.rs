@@ -0,0 +1,310 @@
+use super::*;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// The modules loaded during a run, shared by every `Env` of that run. Each
+// module is evaluated once and its exports are reused until its file changes.
+//
+// Modules are named by the keys of `import`. A name starting with `./` or
+// `../` is relative to the importing file. Any other name is a package-style
+// name, which is either a native module registered by the host, a module of
+// the standard library, such as `std/list`, or looked up in each directory of
+// the search path and then in the directory of the program being run. A name
+// may refer to `<name>.yapl` or to `<name>/index.yapl`. A name with the
+// extension of a data format, such as `./strings.json`, refers to that file,
+// which is read rather than evaluated.
+#[derive(Debug, Default)]
+pub struct Modules {
+    root: PathBuf,
+    search_path: Vec<PathBuf>,
+    host: Arc<Host>,
+    state: Mutex<State>,
+    budget: Option<Arc<Budget>>,
+}
+
+// What the host embedding the interpreter provides to programs.
+#[derive(Debug, Clone, Default)]
+pub struct Host {
+    // Bound on top of the builtins of the program and of every module.
+    pub bindings: Variables,
+    // The exports of native modules, by the name they are imported as.
+    pub modules: Map<String, Value>,
+    // What each run may use.
+    pub limits: Limits,
+}
+
+// Where a module is stored.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Location {
+    File(PathBuf),
+    // A module of the standard library, by name.
+    Std(&'static str),
+    // A module registered by the host, by name.
+    Native(String),
+}
+
+impl Location {
+    // The format of the module when its import doesn't give one.
+    pub fn default_format(&self) -> Format {
+        match self {
+            Location::File(path) => Format::of_extension(path).unwrap_or(Format::Program),
+            Location::Std(_) | Location::Native(_) => Format::Program,
+        }
+    }
+
+    pub fn source(&self) -> Result<Cow<'static, str>, Error> {
+        match self {
+            Location::File(path) => fs::read_to_string(path)
+                .map(Cow::Owned)
+                .map_err(|_| Error::IO),
+            Location::Std(name) => Ok(Cow::Borrowed(stdlib::find(name).unwrap().1)),
+            Location::Native(name) => Err(Error::InvalidOperation(format!(
+                "Native module {} has no source",
+                name
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::File(path) => write!(f, "{}", path.display()),
+            Location::Std(name) => write!(f, "<{}>", name),
+            Location::Native(name) => write!(f, "<native {}>", name),
+        }
+    }
+}
+
+// The environment variable that lists directories to search for modules.
+pub const SEARCH_PATH_VARIABLE: &str = "YAPL_PATH";
+
+// Returns the directories listed by `YAPL_PATH`.
+pub fn search_path_from_env() -> Vec<PathBuf> {
+    match std::env::var_os(SEARCH_PATH_VARIABLE) {
+        Some(paths) => std::env::split_paths(&paths).collect(),
+        None => Vec::new(),
+    }
+}
+
+fn directory_of(file: &str) -> &Path {
+    Path::new(file).parent().unwrap_or_else(|| Path::new(""))
+}
+
+fn is_relative_name(name: &str) -> bool {
+    name.starts_with("./") || name.starts_with("../") || Path::new(name).is_absolute()
+}
+
+// Joins `name` to `directory`, leaving out `.` segments.
+fn join(directory: &Path, name: &str) -> PathBuf {
+    Path::new(name)
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .fold(directory.to_path_buf(), |path, component| {
+            path.join(component)
+        })
+}
+
+// The files a module name may refer to, given the path it names.
+fn candidates(base: &Path) -> Vec<PathBuf> {
+    if Format::of_extension(base).is_some() {
+        return vec![base.to_path_buf()];
+    }
+    let mut file = base.as_os_str().to_owned();
+    file.push(".yapl");
+    vec![PathBuf::from(file), base.join("index.yapl")]
+}
+
+#[derive(Debug, Default)]
+struct State {
+    cache: HashMap<(Location, Format), Module>,
+    // The modules being evaluated, outermost first, used to detect cycles.
+    loading: Vec<Location>,
+}
+
+#[derive(Debug)]
+struct Module {
+    modified: Option<SystemTime>,
+    exports: Value,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+impl Modules {
+    // Creates the modules for a run of the program stored at `file`.
+    pub fn new(file: &str, search_path: Vec<PathBuf>) -> Modules {
+        Modules::with_host(file, search_path, Arc::default())
+    }
+
+    // Like `new`, for a program run by a host that provides `host`.
+    pub fn with_host(file: &str, search_path: Vec<PathBuf>, host: Arc<Host>) -> Modules {
+        let budget = if host.limits.is_unlimited() {
+            None
+        } else {
+            Some(Arc::new(Budget::new(host.limits.clone())))
+        };
+        Modules {
+            root: directory_of(file).to_path_buf(),
+            search_path,
+            host,
+            state: Mutex::default(),
+            budget,
+        }
+    }
+
+    pub fn bindings(&self) -> &Variables {
+        &self.host.bindings
+    }
+
+    // The budget of the run, if the host limits it.
+    pub fn budget(&self) -> Option<&Arc<Budget>> {
+        self.budget.as_ref()
+    }
+
+    // Returns the exports of the native module at `location`, if it is one.
+    pub fn native_exports(&self, location: &Location) -> Option<&Value> {
+        match location {
+            Location::Native(name) => self.host.modules.get(name),
+            _ => None,
+        }
+    }
+
+    // Finds the module `name` imported from the file `importer`.
+    pub fn resolve(&self, importer: &str, name: &str) -> Result<Location, Error> {
+        if self.host.modules.contains_key(name) {
+            return Ok(Location::Native(name.to_string()));
+        }
+        if let Some((name, _)) = stdlib::find(name) {
+            return Ok(Location::Std(name));
+        }
+        let bases: Vec<PathBuf> = if is_relative_name(name) {
+            vec![join(directory_of(importer), name)]
+        } else {
+            self.search_path
+                .iter()
+                .chain(std::iter::once(&self.root))
+                .map(|directory| join(directory, name))
+                .collect()
+        };
+        let mut tried = Vec::new();
+        for candidate in bases.iter().flat_map(|base| candidates(base)) {
+            if candidate.is_file() {
+                return Ok(Location::File(candidate));
+            }
+            tried.push(candidate.display().to_string());
+        }
+        Err(Error::ModuleNotFound(name.to_string(), tried))
+    }
+
+    // Returns the exports of the module at `location`, or its contents when
+    // `format` is a data format, unless it was already loaded and hasn't changed
+    // since. `importer` is the file that imports it, which starts the import
+    // chain when it is the program being run rather than a module.
+    pub fn load(
+        self: &Arc<Modules>,
+        importer: &Path,
+        location: &Location,
+        format: Format,
+    ) -> Result<Value, Error> {
+        if let Location::Native(name) = location {
+            return match (self.native_exports(location), format) {
+                (Some(exports), Format::Program) => Ok(exports.clone()),
+                (Some(_), _) => Err(Error::InvalidOperation(format!(
+                    "Cannot read native module {} as data",
+                    name
+                ))),
+                (None, _) => Err(Error::ModuleNotFound(name.clone(), Vec::new())),
+            };
+        }
+        let (location, modified) = match location {
+            Location::File(path) => {
+                let path = fs::canonicalize(path).map_err(|_| Error::IO)?;
+                let modified = modified(&path);
+                (Location::File(path), modified)
+            }
+            location => (location.clone(), None),
+        };
+        let depth = {
+            let mut state = self.state.lock().unwrap();
+            let depth = state.loading.len();
+            if depth == 0 {
+                state
+                    .loading
+                    .extend(fs::canonicalize(importer).map(Location::File));
+            }
+            if state.loading.contains(&location) {
+                let mut chain: Vec<String> = state
+                    .loading
+                    .iter()
+                    .skip_while(|loading| **loading != location)
+                    .map(|loading| loading.to_string())
+                    .collect();
+                chain.push(location.to_string());
+                state.loading.truncate(depth);
+                return Err(Error::CircularImport(chain));
+            }
+            if let Some(module) = state.cache.get(&(location.clone(), format)) {
+                if module.modified == modified {
+                    let exports = module.exports.clone();
+                    state.loading.truncate(depth);
+                    return Ok(exports);
+                }
+            }
+            state.loading.push(location.clone());
+            depth
+        };
+        let exports = match format {
+            Format::Program => self.evaluate(&location),
+            format => location
+                .source()
+                .and_then(|source| data::parse_data(&source, format)),
+        };
+        let mut state = self.state.lock().unwrap();
+        state.loading.truncate(depth);
+        let exports = exports?;
+        state.cache.insert(
+            (location, format),
+            Module {
+                modified,
+                exports: exports.clone(),
+            },
+        );
+        Ok(exports)
+    }
+
+    // Evaluates a module. Modules of the standard library also see the natives
+    // they are built on.
+    fn evaluate(self: &Arc<Modules>, location: &Location) -> Result<Value, Error> {
+        let parsed_program = parse(&location.source()?)?;
+        let root_env = Env::builtin_with_modules(location.to_string(), self.clone());
+        let env = match location {
+            Location::Std(_) => Env::provided(stdlib::intrinsics(), root_env),
+            _ => root_env,
+        };
+        eval(&env, &parsed_program)
+    }
+
+    // Forgets the module stored at `path`, so that it is evaluated again the
+    // next time it is imported.
+    pub fn invalidate(&self, path: &Path) {
+        if let Ok(path) = fs::canonicalize(path) {
+            let mut state = self.state.lock().unwrap();
+            let path = Location::File(path);
+            state.cache.retain(|(cached, _), _| *cached != path);
+        }
+    }
+
+    // Forgets every loaded module.
+    pub fn clear(&self) {
+        self.state.lock().unwrap().cache.clear();
+    }
+}
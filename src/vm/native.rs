@@ -0,0 +1,577 @@
+use super::*;
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::{DeserializeOwned, DeserializeSeed, MapAccess, Visitor};
+use serde::{forward_to_deserialize_any, Deserializer};
+use std::cell::Cell;
+
+// Natives with typed Rust signatures. Their arguments are converted with
+// `FromValue` and their results with `IntoValue`, so a native can be written
+// as a plain function:
+//
+//     fn string_join(separator: Str, parts: Vec<Str>) -> String {
+//         parts.join(separator.as_str())
+//     }
+//     env.bind_typed_function("string_join", string_join);
+//
+// A native with one parameter takes the whole argument, unless the parameter
+// is `Named`, and one with more takes an array with an element for each
+// parameter. Trailing `Option` parameters may be left out. When the arguments
+// don't fit, the error names the native. Parameters are owned, so a string
+// parameter is a `Str` or a `String` rather than a `&str`.
+
+// How a native takes its arguments, like the `Formals` of a lambda. The
+// arguments are evaluated according to the shape and checked against it before
+// the native is called.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Shape {
+    // A single value, which is whatever the arguments evaluate to.
+    Singleton,
+    // An array of between `min` and `max` values.
+    Positional {
+        min: usize,
+        max: usize,
+    },
+    // An object with each of the `required` keys. Other keys are passed along.
+    Named {
+        required: Vec<Str>,
+        optional: Vec<Str>,
+    },
+}
+
+impl Shape {
+    // Evaluates `args` as arguments of this shape.
+    fn eval_args(&self, env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
+        match (self, args) {
+            (Shape::Positional { .. }, Value::Array(array)) => {
+                Ok(Value::Array(eval_array(env, array)?))
+            }
+            (Shape::Named { .. }, Value::Object(object)) => {
+                Ok(Value::Object(eval_object(env, object)?))
+            }
+            _ => eval(env, args),
+        }
+    }
+
+    fn check(&self, name: &str, args: &Value) -> Result<(), Error> {
+        match self {
+            Shape::Singleton => Ok(()),
+            Shape::Positional { min, max } => {
+                let values =
+                    Value::as_array(args).map_err(|err| invalid_argument(name, describe(err)))?;
+                if values.len() < *min || values.len() > *max {
+                    let expected = if min == max {
+                        min.to_string()
+                    } else {
+                        format!("{} to {}", min, max)
+                    };
+                    return Err(invalid_argument(
+                        name,
+                        format!("Expected {} arguments, got {}", expected, values.len()),
+                    ));
+                }
+                Ok(())
+            }
+            Shape::Named { required, .. } => {
+                let object = match args {
+                    Value::Object(object) => object,
+                    _ => {
+                        let message =
+                            format!("Expected named arguments, got {}", args.type_description());
+                        return Err(invalid_argument(name, message));
+                    }
+                };
+                match required.iter().find(|key| !object.contains_key(*key)) {
+                    Some(key) => Err(invalid_argument(
+                        name,
+                        format!("Missing named argument `{}`", key),
+                    )),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+// A function implemented in Rust, along with the name it is bound to, which
+// errors refer to it by.
+pub struct Native {
+    name: String,
+    shape: Shape,
+    function: NativeFunction,
+}
+
+impl Native {
+    pub fn new(name: &str, shape: Shape, function: NativeFunction) -> Native {
+        Native {
+            name: name.to_string(),
+            shape,
+            function,
+        }
+    }
+
+    pub fn shape(&self) -> &Shape {
+        &self.shape
+    }
+
+    // Evaluates `args` according to the shape, then calls the native.
+    pub fn eval(&self, env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
+        self.call(env, &self.shape.eval_args(env, args)?)
+    }
+
+    // Calls the native with evaluated `args`. A run with limits is checked
+    // again once the native returns, since it may have taken long or built a
+    // large value.
+    pub fn call(&self, env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
+        self.shape.check(&self.name, args)?;
+        env.check_deadline()?;
+        let result = (self.function)(env, args)?;
+        if let Some(budget) = &env.budget {
+            budget.check_deadline()?;
+            budget.check_size(&result)?;
+        }
+        Ok(result)
+    }
+}
+
+// A Rust type that arguments of typed natives can be converted to.
+pub trait FromValue: Sized {
+    // Whether the argument may be left out, which passes null.
+    const OPTIONAL: bool = false;
+
+    fn from_value(value: &Value) -> Result<Self, Error>;
+
+    // The shape of the named arguments, when a native whose only parameter
+    // has this type takes named arguments.
+    fn named_shape() -> Option<Shape> {
+        None
+    }
+}
+
+// A Rust type that typed natives can return.
+pub trait IntoValue {
+    fn into_value(self) -> Result<Value, Error>;
+}
+
+// The named arguments of a native, read into a struct that implements
+// `Deserialize`. The arguments for its `Option` fields may be left out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Named<T>(pub T);
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Value, Error> {
+        Ok(value.clone())
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<bool, Error> {
+        Value::as_bool(value)
+    }
+}
+
+impl FromValue for Number {
+    fn from_value(value: &Value) -> Result<Number, Error> {
+        Value::as_number(value)
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Result<f64, Error> {
+        Value::as_f64(value)
+    }
+}
+
+impl FromValue for Str {
+    fn from_value(value: &Value) -> Result<Str, Error> {
+        Value::as_string(value).cloned()
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<String, Error> {
+        Ok(Value::as_str(value)?.to_string())
+    }
+}
+
+impl FromValue for Object {
+    fn from_value(value: &Value) -> Result<Object, Error> {
+        Value::as_object(value).cloned()
+    }
+}
+
+impl FromValue for Arc<Opaque> {
+    fn from_value(value: &Value) -> Result<Arc<Opaque>, Error> {
+        Value::as_opaque(value).cloned()
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &Value) -> Result<Vec<T>, Error> {
+        Value::as_array(value)?.iter().map(T::from_value).collect()
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    const OPTIONAL: bool = true;
+
+    fn from_value(value: &Value) -> Result<Option<T>, Error> {
+        match value {
+            Value::Null => Ok(None),
+            _ => T::from_value(value).map(Some),
+        }
+    }
+}
+
+impl<T: DeserializeOwned> FromValue for Named<T> {
+    fn from_value(value: &Value) -> Result<Named<T>, Error> {
+        Value::as_object(value)?;
+        from_value(value).map(Named)
+    }
+
+    fn named_shape() -> Option<Shape> {
+        let fields = Cell::new(&[] as &'static [&'static str]);
+        let _ = T::deserialize(FieldsDeserializer(&fields));
+        let (mut required, mut optional) = (Vec::new(), Vec::new());
+        for field in fields.get() {
+            let is_option = Cell::new(false);
+            let _ = T::deserialize(FieldProbe {
+                field,
+                is_option: &is_option,
+                read: false,
+            });
+            match is_option.get() {
+                true => optional.push(Str::key(field)),
+                false => required.push(Str::key(field)),
+            }
+        }
+        Some(Shape::Named { required, optional })
+    }
+}
+
+// Finds the fields of a struct that implements `Deserialize`, which it passes
+// to `deserialize_struct` before reading anything.
+struct FieldsDeserializer<'a>(&'a Cell<&'static [&'static str]>);
+
+impl<'de> Deserializer<'de> for FieldsDeserializer<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(Error::Conversion("Not a struct".to_string()))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.0.set(fields);
+        self.deserialize_any(visitor)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+// Finds whether `field` of a struct that implements `Deserialize` is an
+// `Option`, by reading a struct with only that field, whose value stops the
+// read as soon as it is asked for.
+struct FieldProbe<'a> {
+    field: &'static str,
+    is_option: &'a Cell<bool>,
+    read: bool,
+}
+
+impl<'de> Deserializer<'de> for FieldProbe<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(Error::Conversion("Not a struct".to_string()))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_map(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+impl<'de> MapAccess<'de> for FieldProbe<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        if self.read {
+            return Ok(None);
+        }
+        self.read = true;
+        seed.deserialize(BorrowedStrDeserializer::new(self.field))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        seed.deserialize(OptionProbe(self.is_option))
+    }
+}
+
+// The value of a `FieldProbe`, which notes whether it is read as an `Option`.
+struct OptionProbe<'a>(&'a Cell<bool>);
+
+impl<'de> Deserializer<'de> for OptionProbe<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(Error::Conversion("Not a value".to_string()))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.0.set(true);
+        self.deserialize_any(visitor)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+macro_rules! integer_from_value {
+    ($($integer:ty),*) => {
+        $(
+            impl FromValue for $integer {
+                fn from_value(value: &Value) -> Result<$integer, Error> {
+                    let number = match value {
+                        Value::Number(number) => number,
+                        _ => return Err(Error::invalid_type("integer", value)),
+                    };
+                    // Arithmetic yields floats, so integral ones are accepted.
+                    let integer = match (number.as_i64(), number.as_u64(), number.as_f64()) {
+                        (Some(integer), _, _) => <$integer>::try_from(integer).ok(),
+                        (_, Some(integer), _) => <$integer>::try_from(integer).ok(),
+                        (_, _, Some(float)) if float.is_finite() && float.fract() == 0.0 => {
+                            <$integer>::try_from(float as i128).ok()
+                        }
+                        _ => None,
+                    };
+                    integer.ok_or_else(|| Error::InvalidNumber(number.to_string()))
+                }
+            }
+
+            impl IntoValue for $integer {
+                fn into_value(self) -> Result<Value, Error> {
+                    Ok(Value::Number(self.into()))
+                }
+            }
+        )*
+    };
+}
+
+integer_from_value!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl IntoValue for Value {
+    fn into_value(self) -> Result<Value, Error> {
+        Ok(self)
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Result<Value, Error> {
+        Ok(Value::Null)
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Result<Value, Error> {
+        Ok(Value::Bool(self))
+    }
+}
+
+impl IntoValue for Number {
+    fn into_value(self) -> Result<Value, Error> {
+        Ok(Value::Number(self))
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Result<Value, Error> {
+        to_value(&self)
+    }
+}
+
+impl IntoValue for Str {
+    fn into_value(self) -> Result<Value, Error> {
+        Ok(Value::String(self))
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Result<Value, Error> {
+        Ok(Value::String(Str::from(self)))
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Result<Value, Error> {
+        Ok(Value::String(Str::from(self)))
+    }
+}
+
+impl IntoValue for Object {
+    fn into_value(self) -> Result<Value, Error> {
+        Ok(Value::Object(self))
+    }
+}
+
+impl IntoValue for Opaque {
+    fn into_value(self) -> Result<Value, Error> {
+        Ok(Value::Opaque(Arc::new(self)))
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Result<Value, Error> {
+        let values = self
+            .into_iter()
+            .map(T::into_value)
+            .collect::<Result<Vec<Value>, Error>>()?;
+        Ok(Value::Array(Arc::new(values)))
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Result<Value, Error> {
+        match self {
+            Some(value) => value.into_value(),
+            None => Ok(Value::Null),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Result<T, Error> {
+    fn into_value(self) -> Result<Value, Error> {
+        self?.into_value()
+    }
+}
+
+// A Rust function that can be called as a native, where `Args` is the tuple of
+// its parameter types.
+pub trait TypedFunction<Args>: Send + Sync + 'static {
+    // The shape of the arguments, derived from the parameters.
+    fn shape() -> Shape;
+
+    // Calls the function with `args`, which have the shape of `shape`.
+    fn call_typed(&self, name: &str, args: &Value) -> Result<Value, Error>;
+}
+
+impl Function {
+    // Returns a function value that calls `function` with its arguments
+    // converted to the types of its parameters. `name` is used in errors.
+    pub fn typed<Args, F: TypedFunction<Args>>(name: &str, function: F) -> Value {
+        let callee = name.to_string();
+        Function::native(name, F::shape(), move |_env, args| {
+            function.call_typed(&callee, args)
+        })
+    }
+}
+
+fn invalid_argument(name: &str, message: String) -> Error {
+    Error::InvalidArgument(name.to_string(), message)
+}
+
+fn describe(err: Error) -> String {
+    match err {
+        Error::Conversion(message) | Error::InvalidType(message) => message,
+        Error::InvalidNumber(number) => format!("Invalid number {}", number),
+        err => format!("{:?}", err),
+    }
+}
+
+fn get_argument<T: FromValue>(name: &str, args: &Value, index: usize) -> Result<T, Error> {
+    let args = Value::as_array(args).map_err(|err| invalid_argument(name, describe(err)))?;
+    let value = args.get(index).unwrap_or(&Value::Null);
+    T::from_value(value)
+        .map_err(|err| invalid_argument(name, format!("Argument {}: {}", index + 1, describe(err))))
+}
+
+// The shape of positional parameters, of which the `Option` ones at the end
+// may be left out.
+fn positional_shape(optional: &[bool]) -> Shape {
+    let min = optional
+        .iter()
+        .rposition(|optional| !optional)
+        .map_or(0, |index| index + 1);
+    Shape::Positional {
+        min,
+        max: optional.len(),
+    }
+}
+
+impl<F, R> TypedFunction<()> for F
+where
+    F: Fn() -> R + Send + Sync + 'static,
+    R: IntoValue,
+{
+    fn shape() -> Shape {
+        positional_shape(&[])
+    }
+
+    fn call_typed(&self, _name: &str, _args: &Value) -> Result<Value, Error> {
+        self().into_value()
+    }
+}
+
+impl<F, R, A> TypedFunction<(A,)> for F
+where
+    F: Fn(A) -> R + Send + Sync + 'static,
+    R: IntoValue,
+    A: FromValue,
+{
+    fn shape() -> Shape {
+        A::named_shape().unwrap_or(Shape::Singleton)
+    }
+
+    fn call_typed(&self, name: &str, args: &Value) -> Result<Value, Error> {
+        let arg = A::from_value(args).map_err(|err| invalid_argument(name, describe(err)))?;
+        self(arg).into_value()
+    }
+}
+
+macro_rules! typed_function {
+    ($($arg:ident $index:tt),*) => {
+        impl<F, R, $($arg),*> TypedFunction<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + Send + Sync + 'static,
+            R: IntoValue,
+            $($arg: FromValue),*
+        {
+            fn shape() -> Shape {
+                positional_shape(&[$($arg::OPTIONAL),*])
+            }
+
+            fn call_typed(&self, name: &str, args: &Value) -> Result<Value, Error> {
+                self($(get_argument::<$arg>(name, args, $index)?),*).into_value()
+            }
+        }
+    };
+}
+
+typed_function!(A 0, B 1);
+typed_function!(A 0, B 1, C 2);
+typed_function!(A 0, B 1, C 2, D 3);
+typed_function!(A 0, B 1, C 2, D 3, E 4);
+typed_function!(A 0, B 1, C 2, D 3, E 4, G 5);
@@ -0,0 +1,80 @@
+use super::*;
+use std::any::Any;
+
+// A handle to a resource of the host, such as an open file or a compiled
+// regex, which programs can pass around but not look inside. Natives get the
+// resource back by downcasting:
+//
+//     let regex = Value::as_opaque(value)?.downcast_ref::<Regex>()?;
+//
+// Handles are equal when they hold the same resource.
+pub struct Opaque {
+    type_name: &'static str,
+    value: Arc<dyn Any + Send + Sync>,
+    // What the handle serializes as. Without a placeholder, serializing it is
+    // an error.
+    placeholder: Option<Str>,
+}
+
+impl Opaque {
+    // Wraps `value`. `type_name` identifies the handle: the type
+    // `opaque<type_name>` matches it and errors describe it so, while
+    // `type_of` reports every handle as `opaque`.
+    pub fn new<T: Any + Send + Sync>(type_name: &'static str, value: Arc<T>) -> Opaque {
+        Opaque {
+            type_name,
+            value,
+            placeholder: None,
+        }
+    }
+
+    // Makes the handle serialize as the string `placeholder`.
+    pub fn with_placeholder(mut self, placeholder: &str) -> Opaque {
+        self.placeholder = Some(Str::from(placeholder));
+        self
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    pub fn placeholder(&self) -> Option<&Str> {
+        self.placeholder.as_ref()
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Result<&T, Error> {
+        self.value
+            .downcast_ref()
+            .ok_or_else(|| self.mismatch::<T>())
+    }
+
+    // Like `downcast_ref`, sharing the resource with the handle.
+    pub fn downcast<T: Any + Send + Sync>(&self) -> Result<Arc<T>, Error> {
+        self.value
+            .clone()
+            .downcast()
+            .map_err(|_| self.mismatch::<T>())
+    }
+
+    fn mismatch<T: Any>(&self) -> Error {
+        Error::InvalidType(format!(
+            "Expected {}, got opaque<{}>",
+            std::any::type_name::<T>(),
+            self.type_name
+        ))
+    }
+}
+
+impl std::fmt::Debug for Opaque {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Opaque").field(&self.type_name).finish()
+    }
+}
+
+impl PartialEq for Opaque {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.value, &other.value)
+    }
+}
+
+impl Eq for Opaque {}
@@ -0,0 +1,430 @@
+use super::*;
+
+const BINDER_PREFIX: &str = "$";
+const WILDCARD: &str = "$_";
+const REST_PREFIX: &str = "...";
+
+const TYPE_KEY: &str = "+type";
+const AS_KEY: &str = "+as";
+const LITERAL_KEY: &str = "+literal";
+
+// A structural pattern, as used by the `case` special form and for
+// destructuring in `let` and `lambda`.
+//
+// Match patterns are written as YAML shapes:
+//  * `$name` binds the value to `name`, and `$_` matches anything.
+//  * Other scalars match by equality.
+//  * Arrays match element-wise. A trailing `...$rest` binds the remaining
+//    elements and a trailing `...` ignores them.
+//  * Objects match objects in which every listed key is present and its value
+//    matches. Extra keys are ignored.
+//  * `+type` restricts the type of the value instead (a type name or a list of
+//    type names), `+as` binds the whole value, and `+literal` matches its
+//    argument verbatim.
+//
+// A variable may only be bound once in a pattern.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Pattern {
+    Wildcard,
+    Bind(Arc<String>),
+    Literal(Value),
+    Array {
+        items: Vec<Pattern>,
+        rest: Option<Rest>,
+    },
+    Object {
+        fields: Vec<(String, Pattern)>,
+        types: Option<Vec<String>>,
+        binding: Option<Arc<String>>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Rest {
+    Ignore,
+    Bind(Arc<String>),
+}
+
+impl Pattern {
+    // Parses a match pattern, as used by `case`.
+    pub fn parse(value: &Value) -> Result<Pattern, Error> {
+        parse_match(value)?.check_names()
+    }
+
+    // Parses a binding pattern, as used by `let` and `lambda` formals.
+    //
+    // Binding patterns name variables directly rather than with `$`:
+    //  * `name` binds the value to `name`, and `_` ignores it.
+    //  * `[first, second, ...rest]` destructures an array.
+    //  * `{ user: { name, email } }` destructures an object. A key with a null
+    //    value binds that key, a string value binds it under another name, and
+    //    an array or object value destructures it further.
+    pub fn parse_binding(value: &Value) -> Result<Pattern, Error> {
+        parse_binding(value)?.check_names()
+    }
+
+    // Parses the named formals of a lambda. Keys whose value is an array or
+    // object destructure the argument, and other keys bind it whatever their
+    // value is.
+    pub fn parse_named_formals(object: &Object) -> Result<Pattern, Error> {
+        let fields = object
+            .iter()
+            .map(|(key, value)| {
+                let pattern = match value {
+                    Value::Array(_) | Value::Object(_) => parse_binding(value)?,
+                    _ => Pattern::Bind(Arc::new(key.to_string())),
+                };
+                Ok((key.to_string(), pattern))
+            })
+            .collect::<Result<Vec<(String, Pattern)>, Error>>()?;
+        Pattern::Object {
+            fields,
+            types: None,
+            binding: None,
+        }
+        .check_names()
+    }
+
+    // Returns the names of the variables this pattern binds.
+    pub fn names(&self) -> Vec<Arc<String>> {
+        let mut names = Vec::new();
+        self.collect_names(&mut names);
+        names
+    }
+
+    fn check_names(self) -> Result<Pattern, Error> {
+        let names = self.names();
+        for (index, name) in names.iter().enumerate() {
+            if names[..index].contains(name) {
+                return Err(Error::InvalidPattern(format!(
+                    "`{}` is bound more than once",
+                    name
+                )));
+            }
+        }
+        Ok(self)
+    }
+
+    fn collect_names(&self, names: &mut Vec<Arc<String>>) {
+        match self {
+            Pattern::Wildcard | Pattern::Literal(_) => {}
+            Pattern::Bind(name) => names.push(name.clone()),
+            Pattern::Array { items, rest } => {
+                for item in items.iter() {
+                    item.collect_names(names);
+                }
+                if let Some(Rest::Bind(name)) = rest {
+                    names.push(name.clone());
+                }
+            }
+            Pattern::Object {
+                fields, binding, ..
+            } => {
+                for (_, pattern) in fields.iter() {
+                    pattern.collect_names(names);
+                }
+                if let Some(name) = binding {
+                    names.push(name.clone());
+                }
+            }
+        }
+    }
+
+    pub fn bind(&self, value: &Value, variables: &mut Variables) -> bool {
+        self.match_value(value, variables).is_ok()
+    }
+
+    pub fn destructure(&self, value: &Value, variables: &mut Variables) -> Result<(), Error> {
+        self.match_value(value, variables)
+            .map_err(|mismatch| mismatch.into_error())
+    }
+
+    fn match_value(&self, value: &Value, variables: &mut Variables) -> Result<(), Mismatch> {
+        match self {
+            Pattern::Wildcard => Ok(()),
+            Pattern::Bind(name) => {
+                variables.insert(name.to_string(), value.clone());
+                Ok(())
+            }
+            Pattern::Literal(literal) => {
+                if literal == value {
+                    Ok(())
+                } else {
+                    Err(Mismatch::new(Reason::Literal(literal.clone())))
+                }
+            }
+            Pattern::Array { items, rest } => {
+                let values = match value {
+                    Value::Array(values) => values,
+                    _ => return Err(Mismatch::new(Reason::Type("array", value.clone()))),
+                };
+                let length_matches = match rest {
+                    Some(_) => values.len() >= items.len(),
+                    None => values.len() == items.len(),
+                };
+                if !length_matches {
+                    return Err(Mismatch::new(Reason::Length(items.len(), values.len())));
+                }
+                for (index, (item, value)) in items.iter().zip(values.iter()).enumerate() {
+                    item.match_value(value, variables)
+                        .map_err(|mismatch| mismatch.within(Segment::Index(index)))?;
+                }
+                if let Some(Rest::Bind(name)) = rest {
+                    let remaining = values[items.len()..].to_vec();
+                    variables.insert(name.to_string(), Value::Array(Arc::new(remaining)));
+                }
+                Ok(())
+            }
+            Pattern::Object {
+                fields,
+                types,
+                binding,
+            } => {
+                match types {
+                    Some(types) => {
+                        if !types.iter().any(|name| name == value.type_of()) {
+                            return Err(Mismatch::new(Reason::Type(
+                                "one of the listed types",
+                                value.clone(),
+                            )));
+                        }
+                    }
+                    None => {
+                        if !matches!(value, Value::Object(_)) {
+                            return Err(Mismatch::new(Reason::Type("object", value.clone())));
+                        }
+                    }
+                }
+                if !fields.is_empty() {
+                    let object = match value {
+                        Value::Object(object) => object,
+                        _ => return Err(Mismatch::new(Reason::Type("object", value.clone()))),
+                    };
+                    for (key, pattern) in fields.iter() {
+                        let segment = || Segment::Key(key.clone());
+                        let value = object
+                            .get(key.as_str())
+                            .ok_or_else(|| Mismatch::new(Reason::MissingKey).within(segment()))?;
+                        pattern
+                            .match_value(value, variables)
+                            .map_err(|mismatch| mismatch.within(segment()))?;
+                    }
+                }
+                if let Some(name) = binding {
+                    variables.insert(name.to_string(), value.clone());
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+enum Reason {
+    MissingKey,
+    Type(&'static str, Value),
+    Length(usize, usize),
+    Literal(Value),
+}
+
+// Describes why a value failed to match a pattern. The path is collected in
+// reverse as the failure propagates out of nested patterns.
+struct Mismatch {
+    reversed_path: Vec<Segment>,
+    reason: Reason,
+}
+
+impl Mismatch {
+    fn new(reason: Reason) -> Mismatch {
+        Mismatch {
+            reversed_path: Vec::new(),
+            reason,
+        }
+    }
+
+    fn within(mut self, segment: Segment) -> Mismatch {
+        self.reversed_path.push(segment);
+        self
+    }
+
+    fn path(&self) -> String {
+        let mut path = String::new();
+        for segment in self.reversed_path.iter().rev() {
+            match segment {
+                Segment::Key(key) if path.is_empty() => path.push_str(key),
+                Segment::Key(key) => {
+                    path.push('.');
+                    path.push_str(key);
+                }
+                Segment::Index(index) => path.push_str(&format!("[{}]", index)),
+            }
+        }
+        path
+    }
+
+    fn into_error(self) -> Error {
+        let path = self.path();
+        let location = if path.is_empty() {
+            "top level".to_string()
+        } else {
+            path.clone()
+        };
+        match self.reason {
+            Reason::MissingKey => Error::MissingPath(path),
+            Reason::Type(expected, actual) => Error::PatternMismatch(format!(
+                "Expected {} at {}, got {}",
+                expected,
+                location,
+                actual.type_description()
+            )),
+            Reason::Length(expected, actual) => Error::PatternMismatch(format!(
+                "Expected {} elements at {}, got {}",
+                expected, location, actual
+            )),
+            Reason::Literal(expected) => Error::PatternMismatch(format!(
+                "Expected {} at {}",
+                serialize(&expected).unwrap_or_else(|_| expected.type_of().to_string()),
+                location
+            )),
+        }
+    }
+}
+
+fn parse_match(value: &Value) -> Result<Pattern, Error> {
+    match value {
+        Value::String(string) => Ok(parse_string(string)),
+        Value::Array(values) => parse_array(values, parse_match),
+        Value::Object(object) => parse_object(object),
+        _ => Ok(Pattern::Literal(value.clone())),
+    }
+}
+
+fn parse_binding(value: &Value) -> Result<Pattern, Error> {
+    match value {
+        Value::String(name) if name.as_str() == "_" => Ok(Pattern::Wildcard),
+        Value::String(name) => Ok(Pattern::Bind(Arc::new(name.to_string()))),
+        Value::Array(values) => parse_array(values, parse_binding),
+        Value::Object(object) => {
+            let fields = object
+                .iter()
+                .map(|(key, value)| {
+                    let pattern = match value {
+                        Value::Null => Pattern::Bind(Arc::new(key.to_string())),
+                        _ => parse_binding(value)?,
+                    };
+                    Ok((key.to_string(), pattern))
+                })
+                .collect::<Result<Vec<(String, Pattern)>, Error>>()?;
+            Ok(Pattern::Object {
+                fields,
+                types: None,
+                binding: None,
+            })
+        }
+        _ => Err(Error::invalid_type("binding pattern", value)),
+    }
+}
+
+fn parse_string(string: &Str) -> Pattern {
+    if string.as_str() == WILDCARD {
+        Pattern::Wildcard
+    } else if let Some(name) = string.strip_prefix(BINDER_PREFIX) {
+        Pattern::Bind(Arc::new(name.to_string()))
+    } else {
+        Pattern::Literal(Value::String(string.clone()))
+    }
+}
+
+fn parse_rest(value: &Value) -> Option<Rest> {
+    let rest = match value {
+        Value::String(string) => string.strip_prefix(REST_PREFIX)?,
+        _ => return None,
+    };
+    match rest {
+        "" | WILDCARD => Some(Rest::Ignore),
+        _ => match rest.strip_prefix(BINDER_PREFIX) {
+            Some(name) => Some(Rest::Bind(Arc::new(name.to_string()))),
+            None => Some(Rest::Bind(Arc::new(rest.to_string()))),
+        },
+    }
+}
+
+fn parse_array(
+    values: &[Value],
+    parse_item: fn(&Value) -> Result<Pattern, Error>,
+) -> Result<Pattern, Error> {
+    let mut items = Vec::new();
+    let mut rest = None;
+    for (index, value) in values.iter().enumerate() {
+        if let Some(parsed) = parse_rest(value) {
+            if index + 1 != values.len() {
+                return Err(Error::InvalidPattern(
+                    "Rest pattern must be the last element of an array".to_string(),
+                ));
+            }
+            rest = Some(parsed);
+        } else {
+            items.push(parse_item(value)?);
+        }
+    }
+    Ok(Pattern::Array { items, rest })
+}
+
+fn parse_types(value: &Value) -> Result<Vec<String>, Error> {
+    let names = match value {
+        Value::String(name) => vec![name.to_string()],
+        Value::Array(names) => names
+            .iter()
+            .map(|name| Ok(Value::as_str(name)?.to_string()))
+            .collect::<Result<Vec<String>, Error>>()?,
+        _ => {
+            return Err(Error::invalid_type(
+                "type name or list of type names",
+                value,
+            ))
+        }
+    };
+    for name in names.iter() {
+        if !Value::TYPE_NAMES.contains(&name.as_str()) {
+            return Err(Error::InvalidPattern(format!("Unknown type: {}", name)));
+        }
+    }
+    Ok(names)
+}
+
+fn parse_object(object: &Object) -> Result<Pattern, Error> {
+    if let Some(literal) = object.get(LITERAL_KEY) {
+        if object.len() != 1 {
+            return Err(Error::InvalidPattern(format!(
+                "{} cannot be combined with other keys",
+                LITERAL_KEY
+            )));
+        }
+        return Ok(Pattern::Literal(literal.clone()));
+    }
+    let mut fields = Vec::new();
+    let mut types = None;
+    let mut binding = None;
+    for (key, value) in object.iter() {
+        match key.as_str() {
+            TYPE_KEY => types = Some(parse_types(value)?),
+            AS_KEY => binding = Some(Arc::new(Value::as_str(value)?.to_string())),
+            _ if key.len() > 1 && key.starts_with('+') => {
+                return Err(Error::InvalidPattern(format!(
+                    "Unknown pattern directive: {}",
+                    key
+                )))
+            }
+            _ => fields.push((key.to_string(), parse_match(value)?)),
+        }
+    }
+    Ok(Pattern::Object {
+        fields,
+        types,
+        binding,
+    })
+}
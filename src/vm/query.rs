@@ -0,0 +1,211 @@
+use super::*;
+
+// A query over a value, usually a quoted program. It matches every node that
+// satisfies all of the conditions under `where` and returns either the node or,
+// when `select` is given, the part of it that `select` names.
+//
+// A query is an object with the optional keys:
+//  * `where`, an object of conditions, each of which takes a value or a list
+//    of values to accept:
+//     - `op`, the name of the operation the node calls.
+//     - `key`, the key or index the node is stored under.
+//     - `type`, the type of the node.
+//     - `path`, a path from the root where `*` matches any one segment and
+//       `**` matches any number of them.
+//  * `select`, a path from the matched node. On an operation, the `args`
+//    segment names the arguments to the operation. Nodes without the path are
+//    skipped.
+//
+// Paths are strings of segments separated by `.`, or lists of segments.
+pub struct Query {
+    ops: Option<Vec<String>>,
+    keys: Option<Vec<String>>,
+    types: Option<Vec<String>>,
+    path: Option<Vec<String>>,
+    select: Option<Vec<String>>,
+}
+
+fn invalid(message: String) -> Error {
+    Error::InvalidQuery(message)
+}
+
+fn parse_names(value: &Value) -> Result<Vec<String>, Error> {
+    match value {
+        Value::String(name) => Ok(vec![name.to_string()]),
+        Value::Array(names) => names
+            .iter()
+            .map(|name| Ok(Value::as_str(name)?.to_string()))
+            .collect(),
+        _ => Err(Error::invalid_type("name or list of names", value)),
+    }
+}
+
+pub fn parse_path(value: &Value) -> Result<Vec<String>, Error> {
+    match value {
+        Value::String(path) if path.is_empty() => Ok(Vec::new()),
+        Value::String(path) => Ok(path.split('.').map(str::to_string).collect()),
+        Value::Array(segments) => segments
+            .iter()
+            .map(|segment| match segment {
+                Value::String(key) => Ok(key.to_string()),
+                Value::Number(index) => Ok(index.to_string()),
+                _ => Err(Error::invalid_type("path segment", segment)),
+            })
+            .collect(),
+        _ => Err(Error::invalid_type("path", value)),
+    }
+}
+
+// Returns the name of the operation `object` calls, if it is an operation.
+pub fn op_name(object: &Object) -> Option<&str> {
+    let mut ops = object.keys().filter(|key| is_op_key(key));
+    match (ops.next(), ops.next()) {
+        (Some(name), None) => Some(name),
+        _ => None,
+    }
+}
+
+// Follows `path` down from `value`.
+pub fn select<'a>(value: &'a Value, path: &[String]) -> Option<&'a Value> {
+    let mut current = value;
+    for segment in path.iter() {
+        current = match current {
+            Value::Object(object) => match object.get(segment.as_str()) {
+                Some(value) => value,
+                None if segment == "args" => object.get(op_name(object)?)?,
+                None => return None,
+            },
+            Value::Array(values) => values.get(segment.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+// Whether a segment of a path, which is a key or an index, is `name`.
+fn is_segment(segment: &Value, name: &str) -> bool {
+    match segment {
+        Value::String(key) => key.as_str() == name,
+        Value::Number(index) => index.to_string() == name,
+        _ => false,
+    }
+}
+
+// Matches a path against a pattern in which `*` matches one segment and `**`
+// matches any number of them.
+pub fn match_path(pattern: &[String], path: &[Value]) -> bool {
+    // `matches[i]` is whether the part of the pattern after the segment being
+    // considered matches `path[i..]`. Working back from the end of the pattern
+    // means `**` never retries a suffix of the path, which would take
+    // exponential time when several of them follow each other.
+    let mut matches = vec![false; path.len() + 1];
+    matches[path.len()] = true;
+    for first in pattern.iter().rev() {
+        let mut next = vec![false; path.len() + 1];
+        for index in (0..=path.len()).rev() {
+            next[index] = if first == "**" {
+                matches[index] || (index < path.len() && next[index + 1])
+            } else {
+                index < path.len()
+                    && (first == "*" || is_segment(&path[index], first))
+                    && matches[index + 1]
+            };
+        }
+        matches = next;
+    }
+    matches[0]
+}
+
+impl Query {
+    pub fn parse(value: &Value) -> Result<Query, Error> {
+        let object = Value::as_object(value)?;
+        let mut query = Query {
+            ops: None,
+            keys: None,
+            types: None,
+            path: None,
+            select: None,
+        };
+        for (key, value) in object.iter() {
+            match key.as_str() {
+                "select" => query.select = Some(parse_path(value)?),
+                "where" => {
+                    for (key, value) in Value::as_object(value)?.iter() {
+                        match key.as_str() {
+                            "op" => query.ops = Some(parse_names(value)?),
+                            "key" => query.keys = Some(parse_names(value)?),
+                            "type" => query.types = Some(parse_names(value)?),
+                            "path" => query.path = Some(parse_path(value)?),
+                            _ => return Err(invalid(format!("Unknown condition: {}", key))),
+                        }
+                    }
+                }
+                _ => return Err(invalid(format!("Unknown query key: {}", key))),
+            }
+        }
+        for name in query.types.iter().flatten() {
+            if !Value::TYPE_NAMES.contains(&name.as_str()) {
+                return Err(invalid(format!("Unknown type: {}", name)));
+            }
+        }
+        Ok(query)
+    }
+
+    // Whether `value`, found at `path`, satisfies the conditions of the query.
+    pub fn matches(&self, value: &Value, path: &[Value]) -> bool {
+        let accepts = |names: &Option<Vec<String>>, accept: &dyn Fn(&str) -> bool| match names {
+            Some(names) => names.iter().any(|name| accept(name)),
+            None => true,
+        };
+        let op = match value {
+            Value::Object(object) => op_name(object),
+            _ => None,
+        };
+        accepts(&self.ops, &|name| op == Some(name))
+            && accepts(&self.keys, &|name| {
+                path.last().is_some_and(|segment| is_segment(segment, name))
+            })
+            && accepts(&self.types, &|name| value.type_of() == name)
+            && self
+                .path
+                .as_ref()
+                .is_none_or(|pattern| match_path(pattern, path))
+    }
+
+    // The path from a matched node to the part of it the query selects.
+    pub fn selection(&self) -> &[String] {
+        self.select.as_deref().unwrap_or_default()
+    }
+
+    // Returns the matches in `value`, in the order they appear.
+    pub fn run(&self, value: &Value) -> Vec<Value> {
+        let mut results = Vec::new();
+        self.walk(value, &mut Vec::new(), &mut results);
+        results
+    }
+
+    fn walk(&self, value: &Value, path: &mut Vec<Value>, results: &mut Vec<Value>) {
+        if self.matches(value, path) {
+            if let Some(selected) = select(value, self.selection()) {
+                results.push(selected.clone());
+            }
+        }
+        match value {
+            Value::Array(values) => {
+                for (index, value) in values.iter().enumerate() {
+                    path.push(Value::Number(index.into()));
+                    self.walk(value, path, results);
+                    path.pop();
+                }
+            }
+            Value::Object(object) => {
+                for (key, value) in object.iter() {
+                    path.push(Value::String(key.clone()));
+                    self.walk(value, path, results);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,791 @@
+use super::*;
+use regex::Regex;
+
+// A single failure reported by `validate`. Both paths are JSON pointers: the
+// instance path locates the offending part of the value and the schema path
+// locates the keyword that rejected it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub instance_path: String,
+    pub schema_path: String,
+    pub message: String,
+}
+
+impl ValidationError {
+    pub fn to_value(&self) -> Value {
+        let mut object = ObjectMap::new();
+        let mut insert = |key: &str, value: &str| {
+            object.insert(Str::from(key), Value::String(Str::from(value)));
+        };
+        insert("instance_path", &self.instance_path);
+        insert("schema_path", &self.schema_path);
+        insert("message", &self.message);
+        Value::Object(Arc::new(object))
+    }
+}
+
+// Validates `instance` against a JSON Schema (draft 2020-12 core, applicator,
+// and validation vocabularies). References are resolved within `schema`.
+// Returns every failure found, or an error if the schema itself is malformed or
+// uses a keyword that isn't supported.
+pub fn validate(schema: &Value, instance: &Value) -> Result<Vec<ValidationError>, Error> {
+    let mut validator = Validator::new(schema);
+    validator.validate(schema, instance, "", "")?;
+    Ok(validator.errors)
+}
+
+// A numeric bound keyword, the comparison it requires, and how to describe it.
+type Bound = (&'static str, fn(f64, f64) -> bool, &'static str);
+
+const NUMBER_BOUNDS: [Bound; 4] = [
+    ("maximum", |value, bound| value <= bound, "at most"),
+    (
+        "exclusiveMaximum",
+        |value, bound| value < bound,
+        "less than",
+    ),
+    ("minimum", |value, bound| value >= bound, "at least"),
+    (
+        "exclusiveMinimum",
+        |value, bound| value > bound,
+        "greater than",
+    ),
+];
+
+const JSON_TYPES: [&str; 7] = [
+    "null", "boolean", "integer", "number", "string", "array", "object",
+];
+
+// Keywords that depend on dynamic scope or on annotations, which the validator
+// doesn't track. Ignoring them would accept values they reject.
+const UNSUPPORTED_KEYWORDS: [&str; 3] =
+    ["$dynamicRef", "unevaluatedItems", "unevaluatedProperties"];
+
+struct Validator<'a> {
+    root: &'a Value,
+    anchors: Map<String, &'a Value>,
+    regexes: Map<String, Regex>,
+    active_refs: Vec<(String, String)>,
+    errors: Vec<ValidationError>,
+}
+
+fn invalid_schema(schema_path: &str, message: &str) -> Error {
+    Error::InvalidSchema(format!("{} (at #{})", message, schema_path))
+}
+
+fn unescape_pointer(segment: &str) -> String {
+    segment.replace("~1", "/").replace("~0", "~")
+}
+
+fn collect_anchors<'a>(schema: &'a Value, anchors: &mut Map<String, &'a Value>) {
+    match schema {
+        Value::Object(object) => {
+            for anchor_key in ["$anchor", "$dynamicAnchor"] {
+                if let Some(Value::String(anchor)) = object.get(anchor_key) {
+                    anchors.insert(anchor.to_string(), schema);
+                }
+            }
+            for value in object.values() {
+                collect_anchors(value, anchors);
+            }
+        }
+        Value::Array(values) => {
+            for value in values.iter() {
+                collect_anchors(value, anchors);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Compares two values the way JSON Schema does, where numbers are equal if
+// they have the same mathematical value regardless of representation.
+fn json_equal(lhs: &Value, rhs: &Value) -> bool {
+    match (lhs, rhs) {
+        (Value::Number(lhs), Value::Number(rhs)) => lhs.as_f64() == rhs.as_f64(),
+        (Value::Array(lhs), Value::Array(rhs)) => {
+            lhs.len() == rhs.len() && lhs.iter().zip(rhs.iter()).all(|(l, r)| json_equal(l, r))
+        }
+        (Value::Object(lhs), Value::Object(rhs)) => {
+            lhs.len() == rhs.len()
+                && lhs
+                    .iter()
+                    .all(|(key, l)| rhs.get(key).is_some_and(|r| json_equal(l, r)))
+        }
+        _ => lhs == rhs,
+    }
+}
+
+fn is_integer(number: &Number) -> bool {
+    number.is_i64() || number.is_u64() || number.as_f64().is_some_and(|n| n.fract() == 0.0)
+}
+
+fn has_type(instance: &Value, name: &str) -> bool {
+    match (name, instance) {
+        ("integer", Value::Number(number)) => is_integer(number),
+        (name, instance) => name == json_type(instance),
+    }
+}
+
+// The JSON Schema name of the type of `instance`.
+fn json_type(instance: &Value) -> &str {
+    match instance {
+        Value::Bool(_) => "boolean",
+        _ => instance.type_of(),
+    }
+}
+
+fn schema_number(value: &Value, schema_path: &str) -> Result<f64, Error> {
+    match value {
+        Value::Number(number) => number
+            .as_f64()
+            .ok_or_else(|| invalid_schema(schema_path, "Expected a number")),
+        _ => Err(invalid_schema(schema_path, "Expected a number")),
+    }
+}
+
+fn schema_count(value: &Value, schema_path: &str) -> Result<usize, Error> {
+    match value {
+        Value::Number(number) => number
+            .as_u64()
+            .map(|count| count as usize)
+            .ok_or_else(|| invalid_schema(schema_path, "Expected a non-negative integer")),
+        _ => Err(invalid_schema(
+            schema_path,
+            "Expected a non-negative integer",
+        )),
+    }
+}
+
+fn schema_array<'a>(value: &'a Value, schema_path: &str) -> Result<&'a Vec<Value>, Error> {
+    match value {
+        Value::Array(values) => Ok(values),
+        _ => Err(invalid_schema(schema_path, "Expected an array")),
+    }
+}
+
+fn schema_object<'a>(value: &'a Value, schema_path: &str) -> Result<&'a Object, Error> {
+    match value {
+        Value::Object(object) => Ok(object),
+        _ => Err(invalid_schema(schema_path, "Expected an object")),
+    }
+}
+
+fn schema_strings<'a>(value: &'a Value, schema_path: &str) -> Result<Vec<&'a str>, Error> {
+    schema_array(value, schema_path)?
+        .iter()
+        .map(|value| match value {
+            Value::String(string) => Ok(string.as_str()),
+            _ => Err(invalid_schema(schema_path, "Expected an array of strings")),
+        })
+        .collect()
+}
+
+impl<'a> Validator<'a> {
+    fn new(root: &'a Value) -> Validator<'a> {
+        let mut anchors = Map::new();
+        collect_anchors(root, &mut anchors);
+        Validator {
+            root,
+            anchors,
+            regexes: Map::new(),
+            active_refs: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    fn error(&mut self, instance_path: &str, schema_path: &str, message: String) {
+        self.errors.push(ValidationError {
+            instance_path: instance_path.to_string(),
+            schema_path: schema_path.to_string(),
+            message,
+        });
+    }
+
+    // Validates without recording errors, returning the errors that would
+    // have been recorded.
+    fn probe(
+        &mut self,
+        schema: &'a Value,
+        instance: &Value,
+        instance_path: &str,
+        schema_path: &str,
+    ) -> Result<Vec<ValidationError>, Error> {
+        let saved = std::mem::take(&mut self.errors);
+        let result = self.validate(schema, instance, instance_path, schema_path);
+        let errors = std::mem::replace(&mut self.errors, saved);
+        result?;
+        Ok(errors)
+    }
+
+    fn is_valid(
+        &mut self,
+        schema: &'a Value,
+        instance: &Value,
+        instance_path: &str,
+        schema_path: &str,
+    ) -> Result<bool, Error> {
+        Ok(self
+            .probe(schema, instance, instance_path, schema_path)?
+            .is_empty())
+    }
+
+    fn regex(&mut self, pattern: &str, schema_path: &str) -> Result<&Regex, Error> {
+        if !self.regexes.contains_key(pattern) {
+            let regex = Regex::new(pattern)
+                .map_err(|_| invalid_schema(schema_path, "Invalid regular expression"))?;
+            self.regexes.insert(pattern.to_string(), regex);
+        }
+        Ok(&self.regexes[pattern])
+    }
+
+    fn resolve(&self, reference: &str, schema_path: &str) -> Result<&'a Value, Error> {
+        let unresolvable = || invalid_schema(schema_path, &format!("Cannot resolve {}", reference));
+        let fragment = reference.strip_prefix('#').ok_or_else(unresolvable)?;
+        if fragment.is_empty() {
+            return Ok(self.root);
+        }
+        if !fragment.starts_with('/') {
+            return self.anchors.get(fragment).copied().ok_or_else(unresolvable);
+        }
+        let mut target = self.root;
+        for segment in fragment[1..].split('/') {
+            let segment = unescape_pointer(segment);
+            target = match target {
+                Value::Object(object) => object.get(segment.as_str()),
+                Value::Array(values) => segment
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| values.get(index)),
+                _ => None,
+            }
+            .ok_or_else(unresolvable)?;
+        }
+        Ok(target)
+    }
+
+    fn validate(
+        &mut self,
+        schema: &'a Value,
+        instance: &Value,
+        instance_path: &str,
+        schema_path: &str,
+    ) -> Result<(), Error> {
+        let object = match schema {
+            Value::Bool(true) => return Ok(()),
+            Value::Bool(false) => {
+                self.error(
+                    instance_path,
+                    schema_path,
+                    "No value is allowed here".to_string(),
+                );
+                return Ok(());
+            }
+            Value::Object(object) => object,
+            _ => return Err(invalid_schema(schema_path, "Expected an object or boolean")),
+        };
+        for keyword in UNSUPPORTED_KEYWORDS {
+            if object.contains_key(keyword) {
+                let keyword_path = pointer_child(schema_path, keyword);
+                return Err(invalid_schema(&keyword_path, "Unsupported keyword"));
+            }
+        }
+        if let Some(reference) = object.get("$ref") {
+            let keyword_path = pointer_child(schema_path, "$ref");
+            let reference = match reference {
+                Value::String(reference) => reference.to_string(),
+                _ => return Err(invalid_schema(&keyword_path, "Expected a string")),
+            };
+            self.validate_ref(&reference, instance, instance_path, &keyword_path)?;
+        }
+        self.validate_any(object, instance, instance_path, schema_path)?;
+        self.validate_applicators(object, instance, instance_path, schema_path)?;
+        match instance {
+            Value::Number(number) => {
+                self.validate_number(object, number, instance_path, schema_path)?
+            }
+            Value::String(string) => {
+                self.validate_string(object, string, instance_path, schema_path)?
+            }
+            Value::Array(values) => {
+                self.validate_array(object, values, instance_path, schema_path)?
+            }
+            Value::Object(properties) => {
+                self.validate_object(object, properties, instance_path, schema_path)?
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn validate_ref(
+        &mut self,
+        reference: &str,
+        instance: &Value,
+        instance_path: &str,
+        schema_path: &str,
+    ) -> Result<(), Error> {
+        let key = (reference.to_string(), instance_path.to_string());
+        if self.active_refs.contains(&key) {
+            return Err(invalid_schema(
+                schema_path,
+                &format!("{} refers to itself without consuming input", reference),
+            ));
+        }
+        let target = self.resolve(reference, schema_path)?;
+        self.active_refs.push(key);
+        let result = self.validate(target, instance, instance_path, schema_path);
+        self.active_refs.pop();
+        result
+    }
+
+    // Keywords that apply to instances of every type.
+    fn validate_any(
+        &mut self,
+        object: &'a Object,
+        instance: &Value,
+        instance_path: &str,
+        schema_path: &str,
+    ) -> Result<(), Error> {
+        if let Some(types) = object.get("type") {
+            let keyword_path = pointer_child(schema_path, "type");
+            let names = match types {
+                Value::String(name) => vec![name.as_str()],
+                _ => schema_strings(types, &keyword_path)?,
+            };
+            if let Some(name) = names.iter().find(|name| !JSON_TYPES.contains(name)) {
+                let message = format!("Unknown type `{}`", name);
+                return Err(invalid_schema(&keyword_path, &message));
+            }
+            if !names.iter().any(|name| has_type(instance, name)) {
+                self.error(
+                    instance_path,
+                    &keyword_path,
+                    format!(
+                        "Expected {}, got {}",
+                        names.join(" or "),
+                        json_type(instance)
+                    ),
+                );
+            }
+        }
+        if let Some(allowed) = object.get("enum") {
+            let keyword_path = pointer_child(schema_path, "enum");
+            let allowed = schema_array(allowed, &keyword_path)?;
+            if !allowed.iter().any(|value| json_equal(value, instance)) {
+                self.error(
+                    instance_path,
+                    &keyword_path,
+                    "Value is not one of the allowed values".to_string(),
+                );
+            }
+        }
+        if let Some(constant) = object.get("const") {
+            if !json_equal(constant, instance) {
+                self.error(
+                    instance_path,
+                    &pointer_child(schema_path, "const"),
+                    "Value does not equal the constant".to_string(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_applicators(
+        &mut self,
+        object: &'a Object,
+        instance: &Value,
+        instance_path: &str,
+        schema_path: &str,
+    ) -> Result<(), Error> {
+        if let Some(schemas) = object.get("allOf") {
+            let keyword_path = pointer_child(schema_path, "allOf");
+            for (index, schema) in schema_array(schemas, &keyword_path)?.iter().enumerate() {
+                let path = pointer_child(&keyword_path, &index.to_string());
+                self.validate(schema, instance, instance_path, &path)?;
+            }
+        }
+        if let Some(schemas) = object.get("anyOf") {
+            let keyword_path = pointer_child(schema_path, "anyOf");
+            let mut any = false;
+            for (index, schema) in schema_array(schemas, &keyword_path)?.iter().enumerate() {
+                let path = pointer_child(&keyword_path, &index.to_string());
+                if self.is_valid(schema, instance, instance_path, &path)? {
+                    any = true;
+                    break;
+                }
+            }
+            if !any {
+                self.error(
+                    instance_path,
+                    &keyword_path,
+                    "Value does not match any of the schemas".to_string(),
+                );
+            }
+        }
+        if let Some(schemas) = object.get("oneOf") {
+            let keyword_path = pointer_child(schema_path, "oneOf");
+            let mut matches = 0;
+            for (index, schema) in schema_array(schemas, &keyword_path)?.iter().enumerate() {
+                let path = pointer_child(&keyword_path, &index.to_string());
+                if self.is_valid(schema, instance, instance_path, &path)? {
+                    matches += 1;
+                }
+            }
+            if matches != 1 {
+                self.error(
+                    instance_path,
+                    &keyword_path,
+                    format!(
+                        "Value matches {} of the schemas, expected exactly 1",
+                        matches
+                    ),
+                );
+            }
+        }
+        if let Some(schema) = object.get("not") {
+            let keyword_path = pointer_child(schema_path, "not");
+            if self.is_valid(schema, instance, instance_path, &keyword_path)? {
+                self.error(
+                    instance_path,
+                    &keyword_path,
+                    "Value must not match the schema".to_string(),
+                );
+            }
+        }
+        if let Some(condition) = object.get("if") {
+            let condition_path = pointer_child(schema_path, "if");
+            let branch = if self.is_valid(condition, instance, instance_path, &condition_path)? {
+                "then"
+            } else {
+                "else"
+            };
+            if let Some(schema) = object.get(branch) {
+                self.validate(
+                    schema,
+                    instance,
+                    instance_path,
+                    &pointer_child(schema_path, branch),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_number(
+        &mut self,
+        object: &'a Object,
+        number: &Number,
+        instance_path: &str,
+        schema_path: &str,
+    ) -> Result<(), Error> {
+        let value = match number.as_f64() {
+            Some(value) => value,
+            None => return Ok(()),
+        };
+        if let Some(divisor) = object.get("multipleOf") {
+            let keyword_path = pointer_child(schema_path, "multipleOf");
+            let divisor = schema_number(divisor, &keyword_path)?;
+            let quotient = value / divisor;
+            if !quotient.is_finite() || (quotient - quotient.round()).abs() > 1e-9 {
+                self.error(
+                    instance_path,
+                    &keyword_path,
+                    format!("Expected a multiple of {}", divisor),
+                );
+            }
+        }
+        for (keyword, within, description) in NUMBER_BOUNDS {
+            if let Some(bound) = object.get(keyword) {
+                let keyword_path = pointer_child(schema_path, keyword);
+                let bound = schema_number(bound, &keyword_path)?;
+                if !within(value, bound) {
+                    self.error(
+                        instance_path,
+                        &keyword_path,
+                        format!("Expected a number {} {}, got {}", description, bound, value),
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_string(
+        &mut self,
+        object: &'a Object,
+        string: &str,
+        instance_path: &str,
+        schema_path: &str,
+    ) -> Result<(), Error> {
+        let length = string.chars().count();
+        if let Some(limit) = object.get("maxLength") {
+            let keyword_path = pointer_child(schema_path, "maxLength");
+            let limit = schema_count(limit, &keyword_path)?;
+            if length > limit {
+                self.error(
+                    instance_path,
+                    &keyword_path,
+                    format!("Expected at most {} characters, got {}", limit, length),
+                );
+            }
+        }
+        if let Some(limit) = object.get("minLength") {
+            let keyword_path = pointer_child(schema_path, "minLength");
+            let limit = schema_count(limit, &keyword_path)?;
+            if length < limit {
+                self.error(
+                    instance_path,
+                    &keyword_path,
+                    format!("Expected at least {} characters, got {}", limit, length),
+                );
+            }
+        }
+        if let Some(pattern) = object.get("pattern") {
+            let keyword_path = pointer_child(schema_path, "pattern");
+            let pattern = match pattern {
+                Value::String(pattern) => pattern,
+                _ => return Err(invalid_schema(&keyword_path, "Expected a string")),
+            };
+            if !self.regex(pattern, &keyword_path)?.is_match(string) {
+                self.error(
+                    instance_path,
+                    &keyword_path,
+                    format!("Expected a string matching {}", pattern),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_array(
+        &mut self,
+        object: &'a Object,
+        values: &[Value],
+        instance_path: &str,
+        schema_path: &str,
+    ) -> Result<(), Error> {
+        let mut prefix_length = 0;
+        if let Some(schemas) = object.get("prefixItems") {
+            let keyword_path = pointer_child(schema_path, "prefixItems");
+            let schemas = schema_array(schemas, &keyword_path)?;
+            prefix_length = schemas.len();
+            for (index, (schema, value)) in schemas.iter().zip(values.iter()).enumerate() {
+                self.validate(
+                    schema,
+                    value,
+                    &pointer_child(instance_path, &index.to_string()),
+                    &pointer_child(&keyword_path, &index.to_string()),
+                )?;
+            }
+        }
+        if let Some(schema) = object.get("items") {
+            let keyword_path = pointer_child(schema_path, "items");
+            for (index, value) in values.iter().enumerate().skip(prefix_length) {
+                let path = pointer_child(instance_path, &index.to_string());
+                self.validate(schema, value, &path, &keyword_path)?;
+            }
+        }
+        if let Some(schema) = object.get("contains") {
+            let keyword_path = pointer_child(schema_path, "contains");
+            let mut count = 0;
+            for (index, value) in values.iter().enumerate() {
+                let path = pointer_child(instance_path, &index.to_string());
+                if self.is_valid(schema, value, &path, &keyword_path)? {
+                    count += 1;
+                }
+            }
+            let minimum = match object.get("minContains") {
+                Some(minimum) => schema_count(minimum, &pointer_child(schema_path, "minContains"))?,
+                None => 1,
+            };
+            if count < minimum {
+                self.error(
+                    instance_path,
+                    &keyword_path,
+                    format!(
+                        "Expected at least {} matching elements, got {}",
+                        minimum, count
+                    ),
+                );
+            }
+            if let Some(maximum) = object.get("maxContains") {
+                let maximum_path = pointer_child(schema_path, "maxContains");
+                let maximum = schema_count(maximum, &maximum_path)?;
+                if count > maximum {
+                    self.error(
+                        instance_path,
+                        &maximum_path,
+                        format!(
+                            "Expected at most {} matching elements, got {}",
+                            maximum, count
+                        ),
+                    );
+                }
+            }
+        }
+        if let Some(limit) = object.get("maxItems") {
+            let keyword_path = pointer_child(schema_path, "maxItems");
+            let limit = schema_count(limit, &keyword_path)?;
+            if values.len() > limit {
+                self.error(
+                    instance_path,
+                    &keyword_path,
+                    format!("Expected at most {} elements, got {}", limit, values.len()),
+                );
+            }
+        }
+        if let Some(limit) = object.get("minItems") {
+            let keyword_path = pointer_child(schema_path, "minItems");
+            let limit = schema_count(limit, &keyword_path)?;
+            if values.len() < limit {
+                self.error(
+                    instance_path,
+                    &keyword_path,
+                    format!("Expected at least {} elements, got {}", limit, values.len()),
+                );
+            }
+        }
+        if let Some(Value::Bool(true)) = object.get("uniqueItems") {
+            let duplicate = values.iter().enumerate().any(|(index, value)| {
+                values[index + 1..]
+                    .iter()
+                    .any(|other| json_equal(value, other))
+            });
+            if duplicate {
+                self.error(
+                    instance_path,
+                    &pointer_child(schema_path, "uniqueItems"),
+                    "Expected unique elements".to_string(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_object(
+        &mut self,
+        object: &'a Object,
+        properties: &Object,
+        instance_path: &str,
+        schema_path: &str,
+    ) -> Result<(), Error> {
+        let mut evaluated: Vec<&str> = Vec::new();
+        if let Some(schemas) = object.get("properties") {
+            let keyword_path = pointer_child(schema_path, "properties");
+            for (name, schema) in schema_object(schemas, &keyword_path)?.iter() {
+                if let Some(value) = properties.get(name) {
+                    evaluated.push(name);
+                    self.validate(
+                        schema,
+                        value,
+                        &pointer_child(instance_path, name),
+                        &pointer_child(&keyword_path, name),
+                    )?;
+                }
+            }
+        }
+        if let Some(schemas) = object.get("patternProperties") {
+            let keyword_path = pointer_child(schema_path, "patternProperties");
+            for (pattern, schema) in schema_object(schemas, &keyword_path)?.iter() {
+                let pattern_path = pointer_child(&keyword_path, pattern);
+                for (name, value) in properties.iter() {
+                    if self.regex(pattern, &pattern_path)?.is_match(name) {
+                        evaluated.push(name);
+                        let path = pointer_child(instance_path, name);
+                        self.validate(schema, value, &path, &pattern_path)?;
+                    }
+                }
+            }
+        }
+        if let Some(schema) = object.get("additionalProperties") {
+            let keyword_path = pointer_child(schema_path, "additionalProperties");
+            for (name, value) in properties.iter() {
+                if !evaluated.contains(&name.as_str()) {
+                    let path = pointer_child(instance_path, name);
+                    self.validate(schema, value, &path, &keyword_path)?;
+                }
+            }
+        }
+        if let Some(schema) = object.get("propertyNames") {
+            let keyword_path = pointer_child(schema_path, "propertyNames");
+            for name in properties.keys() {
+                let name_value = Value::String(name.clone());
+                let path = pointer_child(instance_path, name);
+                self.validate(schema, &name_value, &path, &keyword_path)?;
+            }
+        }
+        if let Some(required) = object.get("required") {
+            let keyword_path = pointer_child(schema_path, "required");
+            for name in schema_strings(required, &keyword_path)? {
+                if !properties.contains_key(name) {
+                    self.error(
+                        instance_path,
+                        &keyword_path,
+                        format!("Missing required property `{}`", name),
+                    );
+                }
+            }
+        }
+        if let Some(dependencies) = object.get("dependentRequired") {
+            let keyword_path = pointer_child(schema_path, "dependentRequired");
+            for (name, required) in schema_object(dependencies, &keyword_path)?.iter() {
+                if !properties.contains_key(name) {
+                    continue;
+                }
+                let dependency_path = pointer_child(&keyword_path, name);
+                for dependency in schema_strings(required, &dependency_path)? {
+                    if !properties.contains_key(dependency) {
+                        self.error(
+                            instance_path,
+                            &dependency_path,
+                            format!("Property `{}` requires `{}`", name, dependency),
+                        );
+                    }
+                }
+            }
+        }
+        if let Some(dependencies) = object.get("dependentSchemas") {
+            let keyword_path = pointer_child(schema_path, "dependentSchemas");
+            for (name, schema) in schema_object(dependencies, &keyword_path)?.iter() {
+                if properties.contains_key(name) {
+                    let instance = Value::Object(properties.clone());
+                    let path = pointer_child(&keyword_path, name);
+                    self.validate(schema, &instance, instance_path, &path)?;
+                }
+            }
+        }
+        if let Some(limit) = object.get("maxProperties") {
+            let keyword_path = pointer_child(schema_path, "maxProperties");
+            let limit = schema_count(limit, &keyword_path)?;
+            if properties.len() > limit {
+                self.error(
+                    instance_path,
+                    &keyword_path,
+                    format!(
+                        "Expected at most {} properties, got {}",
+                        limit,
+                        properties.len()
+                    ),
+                );
+            }
+        }
+        if let Some(limit) = object.get("minProperties") {
+            let keyword_path = pointer_child(schema_path, "minProperties");
+            let limit = schema_count(limit, &keyword_path)?;
+            if properties.len() < limit {
+                self.error(
+                    instance_path,
+                    &keyword_path,
+                    format!(
+                        "Expected at least {} properties, got {}",
+                        limit,
+                        properties.len()
+                    ),
+                );
+            }
+        }
+        Ok(())
+    }
+}
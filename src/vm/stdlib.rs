@@ -0,0 +1,138 @@
+use super::*;
+
+// The version of the standard library, which changes with the interpreter
+// that embeds it.
+pub const STD_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// The module whose exports are bound before a program runs, when the prelude
+// is enabled.
+pub const PRELUDE: &str = "std/prelude";
+
+// The modules of the standard library, by the name they are imported as.
+const MODULES: [(&str, &str); 5] = [
+    ("std", include_str!("std/index.yapl")),
+    ("std/list", include_str!("std/list.yapl")),
+    ("std/prelude", include_str!("std/prelude.yapl")),
+    ("std/string", include_str!("std/string.yapl")),
+    ("std/test", include_str!("std/test.yapl")),
+];
+
+// Returns the name and source of the standard module `name`, if there is one.
+pub fn find(name: &str) -> Option<(&'static str, &'static str)> {
+    MODULES.iter().copied().find(|(module, _)| *module == name)
+}
+
+// The natives the standard modules are built on, which programs only see
+// through the modules' exports.
+pub fn intrinsics() -> Variables {
+    let mut env = Env {
+        variables: Variables::new(),
+        parent: None,
+        modules: None,
+        budget: None,
+        provided: true,
+    };
+    env.bind_string("std_version", STD_VERSION.to_string());
+    env.bind_native_function(
+        "list_filter",
+        Shape::Positional { min: 2, max: 2 },
+        list_filter,
+    );
+    env.bind_native_function("list_fold", Shape::Positional { min: 3, max: 3 }, list_fold);
+    env.bind_native_function("list_reverse", Shape::Singleton, list_reverse);
+    env.bind_native_function("string_concat", Shape::Singleton, string_concat);
+    env.bind_native_function(
+        "string_join",
+        Shape::Positional { min: 2, max: 2 },
+        string_join,
+    );
+    env.bind_typed_function("string_length", string_length);
+    env.bind_typed_function("string_lower", string_lower);
+    env.bind_native_function(
+        "string_split",
+        Shape::Positional { min: 2, max: 2 },
+        string_split,
+    );
+    env.bind_typed_function("string_trim", string_trim);
+    env.bind_typed_function("string_upper", string_upper);
+    env.variables
+}
+
+// Folds `f` over `xs` from the left, starting with `init`. It iterates rather
+// than recursing, so long lists don't exhaust the stack.
+fn list_fold(env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
+    let args = Value::as_array(args)?;
+    let function = Value::as_function(get_index(args, 0)?)?;
+    let xs = Value::as_array(get_index(args, 2)?)?;
+    xs.iter().try_fold(get_index(args, 1)?.clone(), |acc, x| {
+        function.call(env, &Value::Array(Arc::new(vec![acc, x.clone()])))
+    })
+}
+
+// Keeps the elements of `xs` for which `f` returns true. Like `list_fold`,
+// it builds the result in place rather than copying it at each step.
+fn list_filter(env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
+    let args = Value::as_array(args)?;
+    let function = Value::as_function(get_index(args, 0)?)?;
+    let xs = Value::as_array(get_index(args, 1)?)?;
+    let mut kept = Vec::new();
+    for x in xs.iter() {
+        if Value::as_bool(&function.call(env, x)?)? {
+            env.check_length(kept.len() + 1)?;
+            kept.push(x.clone());
+        }
+    }
+    Ok(Value::Array(Arc::new(kept)))
+}
+
+fn list_reverse(env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
+    let xs = Value::as_array(args)?;
+    env.check_length(xs.len())?;
+    Ok(Value::Array(Arc::new(xs.iter().rev().cloned().collect())))
+}
+
+// Checks the length of the string before building it, since it may be much
+// longer than any of its parts.
+fn string_concat(env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
+    let parts = Vec::<Str>::from_value(args)?;
+    env.check_length(parts.iter().map(|part| part.len()).sum())?;
+    Ok(Value::String(Str::from(parts.concat())))
+}
+
+fn string_join(env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
+    let args = Value::as_array(args)?;
+    let separator = Value::as_str(get_index(args, 0)?)?;
+    let parts = Vec::<Str>::from_value(get_index(args, 1)?)?;
+    let separators = parts.len().saturating_sub(1) * separator.len();
+    env.check_length(parts.iter().map(|part| part.len()).sum::<usize>() + separators)?;
+    Ok(Value::String(Str::from(parts.join(separator))))
+}
+
+fn string_length(string: Str) -> usize {
+    string.chars().count()
+}
+
+fn string_lower(string: Str) -> String {
+    string.to_lowercase()
+}
+
+// Counts the parts before building them, since a short string may split into
+// many parts.
+fn string_split(env: &Arc<Env>, args: &Value) -> Result<Value, Error> {
+    let args = Value::as_array(args)?;
+    let separator = Value::as_str(get_index(args, 0)?)?;
+    let string = Value::as_str(get_index(args, 1)?)?;
+    env.check_length(string.split(separator).count())?;
+    let parts = string.split(separator);
+    Ok(Value::Array(Arc::new(
+        parts.map(|part| Value::String(Str::from(part))).collect(),
+    )))
+}
+
+fn string_trim(string: Str) -> Str {
+    Str::from(string.trim())
+}
+
+fn string_upper(string: Str) -> String {
+    string.to_uppercase()
+}
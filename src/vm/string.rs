@@ -0,0 +1,175 @@
+use lazy_static::lazy_static;
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+
+// The longest string stored without a heap allocation. Together with the
+// length and the discriminant, it keeps a `Str` at 24 bytes, which is no
+// larger than a `String`.
+const INLINE_CAPACITY: usize = 22;
+
+// An immutable string. Short strings, which include most object keys and many
+// values in typical datasets, are stored inline; longer ones share an
+// allocation when cloned.
+#[derive(Clone)]
+pub struct Str(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Inline(u8, [u8; INLINE_CAPACITY]),
+    Heap(Arc<str>),
+}
+
+impl Str {
+    // Returns a `Str` for an object key. Keys that don't fit inline are
+    // interned, so a key repeated across millions of objects is stored once.
+    pub fn key(string: &str) -> Str {
+        match Str::inline(string) {
+            Some(key) => key,
+            None => Str(Repr::Heap(intern(string))),
+        }
+    }
+
+    fn inline(string: &str) -> Option<Str> {
+        if string.len() > INLINE_CAPACITY {
+            return None;
+        }
+        let mut bytes = [0; INLINE_CAPACITY];
+        bytes[..string.len()].copy_from_slice(string.as_bytes());
+        Some(Str(Repr::Inline(string.len() as u8, bytes)))
+    }
+
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            // The bytes were copied from a `str`, so they are valid UTF-8.
+            Repr::Inline(len, bytes) => unsafe {
+                std::str::from_utf8_unchecked(&bytes[..*len as usize])
+            },
+            Repr::Heap(string) => string,
+        }
+    }
+}
+
+struct Interner {
+    strings: HashSet<Arc<str>>,
+    // The size at which strings no longer used outside the interner are
+    // dropped, so that unique keys don't accumulate forever.
+    limit: usize,
+}
+
+const MIN_INTERNER_LIMIT: usize = 1024;
+
+lazy_static! {
+    static ref INTERNER: Mutex<Interner> = Mutex::new(Interner {
+        strings: HashSet::new(),
+        limit: MIN_INTERNER_LIMIT,
+    });
+}
+
+fn intern(string: &str) -> Arc<str> {
+    let mut interner = INTERNER.lock().unwrap();
+    if let Some(interned) = interner.strings.get(string) {
+        return interned.clone();
+    }
+    if interner.strings.len() >= interner.limit {
+        interner
+            .strings
+            .retain(|interned| Arc::strong_count(interned) > 1);
+        interner.limit = MIN_INTERNER_LIMIT.max(interner.strings.len() * 2);
+    }
+    let interned: Arc<str> = Arc::from(string);
+    interner.strings.insert(interned.clone());
+    interned
+}
+
+impl From<&str> for Str {
+    fn from(string: &str) -> Str {
+        Str::inline(string).unwrap_or_else(|| Str(Repr::Heap(Arc::from(string))))
+    }
+}
+
+impl From<String> for Str {
+    fn from(string: String) -> Str {
+        Str::inline(&string).unwrap_or_else(|| Str(Repr::Heap(Arc::from(string))))
+    }
+}
+
+impl From<&String> for Str {
+    fn from(string: &String) -> Str {
+        Str::from(string.as_str())
+    }
+}
+
+impl Deref for Str {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for Str {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Str {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for Str {
+    fn eq(&self, other: &Str) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Str {}
+
+impl PartialEq<str> for Str {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Str {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialOrd for Str {
+    fn partial_cmp(&self, other: &Str) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Str {
+    fn cmp(&self, other: &Str) -> std::cmp::Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+// Hashes like a `str`, so that maps keyed by `Str` can be queried with `&str`.
+impl Hash for Str {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl fmt::Debug for Str {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Str {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
@@ -0,0 +1,313 @@
+use super::*;
+use std::fmt;
+
+const ARRAY_KEY: &str = "+array";
+const OBJECT_KEY: &str = "+object";
+
+// A runtime type annotation, as used by `+types` and `+returns` on `lambda`.
+//
+// Types are written either as strings or as YAML shapes:
+//  * `any`, `null`, `bool`, `number`, `string`, `array`, `object`, and
+//    `function` name the basic types.
+//  * `opaque` is any handle of the host, and `opaque<regex>` one that the
+//    host named `regex`.
+//  * `array<T>` is an array whose elements are all `T`, and `object<T>` is an
+//    object whose values are all `T`.
+//  * `T | U` is a union, and `T?` is shorthand for `T | null`.
+//  * An object such as `{ name: string, email?: string }` is a shape. Keys
+//    ending in `?` are optional and extra keys are allowed.
+//  * A list such as `[string, { id: number }]` is a union of its entries.
+//  * `{ +array: T }` and `{ +object: T }` are the shape equivalents of
+//    `array<T>` and `object<T>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    Any,
+    Null,
+    Bool,
+    Number,
+    String,
+    Function,
+    Opaque(Option<String>),
+    Array(Box<Type>),
+    Object(Box<Type>),
+    Shape(Vec<Field>),
+    Union(Vec<Type>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    pub optional: bool,
+    pub field_type: Type,
+}
+
+impl Type {
+    pub fn parse(value: &Value) -> Result<Type, Error> {
+        match value {
+            Value::String(source) => TypeParser::new(source).parse(),
+            Value::Array(alternatives) => Ok(Type::Union(
+                alternatives
+                    .iter()
+                    .map(Type::parse)
+                    .collect::<Result<Vec<Type>, Error>>()?,
+            )),
+            Value::Object(object) => parse_shape(object),
+            _ => Err(Error::invalid_type(
+                "type (string, array, or object)",
+                value,
+            )),
+        }
+    }
+
+    // Returns a description of the first part of `value` that does not match,
+    // or `None` if the value has this type.
+    pub fn check(&self, value: &Value, path: &str) -> Option<String> {
+        let matches = match (self, value) {
+            (Type::Any, _) => true,
+            (Type::Null, Value::Null) => true,
+            (Type::Bool, Value::Bool(_)) => true,
+            (Type::Number, Value::Number(_)) => true,
+            (Type::String, Value::String(_)) => true,
+            (Type::Function, Value::Function(_)) => true,
+            (Type::Opaque(None), Value::Opaque(_)) => true,
+            (Type::Opaque(Some(name)), Value::Opaque(opaque)) => opaque.type_name() == name,
+            (Type::Array(element), Value::Array(values)) => {
+                return values.iter().enumerate().find_map(|(index, value)| {
+                    element.check(value, &format!("{}[{}]", path, index))
+                });
+            }
+            (Type::Object(element), Value::Object(object)) => {
+                return object
+                    .iter()
+                    .find_map(|(key, value)| element.check(value, &format!("{}.{}", path, key)));
+            }
+            (Type::Shape(fields), Value::Object(object)) => {
+                return fields.iter().find_map(|field| {
+                    let path = format!("{}.{}", path, field.name);
+                    match object.get(field.name.as_str()) {
+                        Some(value) => field.field_type.check(value, &path),
+                        None if field.optional => None,
+                        None => Some(format!("missing required field `{}`", path)),
+                    }
+                });
+            }
+            (Type::Union(alternatives), _) => {
+                // If any alternative matches, there are no problems to report.
+                let problems = alternatives
+                    .iter()
+                    .map(|alternative| alternative.check(value, path))
+                    .collect::<Option<Vec<String>>>()?;
+                // When only one alternative has the right kind of value, its
+                // problem is more useful than a summary of the whole union.
+                let mut candidates = alternatives
+                    .iter()
+                    .zip(problems)
+                    .filter(|(alternative, _)| alternative.accepts_kind_of(value));
+                match (candidates.next(), candidates.next()) {
+                    (Some((_, problem)), None) => return Some(problem),
+                    _ => false,
+                }
+            }
+            _ => false,
+        };
+        if matches {
+            None
+        } else {
+            Some(format!(
+                "expected {} at `{}`, got {}",
+                self,
+                path,
+                value.type_description()
+            ))
+        }
+    }
+
+    fn accepts_kind_of(&self, value: &Value) -> bool {
+        match (self, value) {
+            (Type::Array(_), Value::Array(_)) => true,
+            (Type::Object(_), Value::Object(_)) | (Type::Shape(_), Value::Object(_)) => true,
+            (Type::Union(alternatives), _) => alternatives
+                .iter()
+                .any(|alternative| alternative.accepts_kind_of(value)),
+            _ => self.check(value, "").is_none(),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Any => write!(f, "any"),
+            Type::Null => write!(f, "null"),
+            Type::Bool => write!(f, "bool"),
+            Type::Number => write!(f, "number"),
+            Type::String => write!(f, "string"),
+            Type::Function => write!(f, "function"),
+            Type::Opaque(None) => write!(f, "opaque"),
+            Type::Opaque(Some(name)) => write!(f, "opaque<{}>", name),
+            Type::Array(element) if **element == Type::Any => write!(f, "array"),
+            Type::Array(element) => write!(f, "array<{}>", element),
+            Type::Object(element) if **element == Type::Any => write!(f, "object"),
+            Type::Object(element) => write!(f, "object<{}>", element),
+            Type::Shape(fields) => {
+                write!(f, "{{")?;
+                for (index, field) in fields.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    let optional = if field.optional { "?" } else { "" };
+                    write!(f, " {}{}: {}", field.name, optional, field.field_type)?;
+                }
+                write!(f, " }}")
+            }
+            Type::Union(alternatives) => {
+                for (index, alternative) in alternatives.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, " | ")?;
+                    }
+                    write!(f, "{}", alternative)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn parse_shape(object: &Object) -> Result<Type, Error> {
+    if let Some(element) = object.get(ARRAY_KEY) {
+        return Ok(Type::Array(Box::new(Type::parse(element)?)));
+    }
+    if let Some(element) = object.get(OBJECT_KEY) {
+        return Ok(Type::Object(Box::new(Type::parse(element)?)));
+    }
+    let fields = object
+        .iter()
+        .map(|(key, value)| {
+            let (name, optional) = match key.strip_suffix('?') {
+                Some(name) => (name.to_string(), true),
+                None => (key.to_string(), false),
+            };
+            Ok(Field {
+                name,
+                optional,
+                field_type: Type::parse(value)?,
+            })
+        })
+        .collect::<Result<Vec<Field>, Error>>()?;
+    Ok(Type::Shape(fields))
+}
+
+// A recursive descent parser for the string form of types:
+//
+//   union   := postfix ('|' postfix)*
+//   postfix := primary '?'*
+//   primary := name ('<' union '>')? | 'opaque' ('<' name '>')?
+struct TypeParser<'a> {
+    source: &'a str,
+    rest: &'a str,
+}
+
+impl<'a> TypeParser<'a> {
+    fn new(source: &'a str) -> TypeParser<'a> {
+        TypeParser {
+            source,
+            rest: source,
+        }
+    }
+
+    fn error(&self) -> Error {
+        Error::InvalidType(format!("Malformed type: {}", self.source))
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    fn eat(&mut self, token: char) -> bool {
+        self.skip_whitespace();
+        match self.rest.strip_prefix(token) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn parse(mut self) -> Result<Type, Error> {
+        let parsed = self.parse_union()?;
+        self.skip_whitespace();
+        if !self.rest.is_empty() {
+            return Err(self.error());
+        }
+        Ok(parsed)
+    }
+
+    fn parse_union(&mut self) -> Result<Type, Error> {
+        let mut alternatives = vec![self.parse_postfix()?];
+        while self.eat('|') {
+            alternatives.push(self.parse_postfix()?);
+        }
+        if alternatives.len() == 1 {
+            Ok(alternatives.pop().unwrap())
+        } else {
+            Ok(Type::Union(alternatives))
+        }
+    }
+
+    fn parse_postfix(&mut self) -> Result<Type, Error> {
+        let mut parsed = self.parse_primary()?;
+        while self.eat('?') {
+            parsed = Type::Union(vec![parsed, Type::Null]);
+        }
+        Ok(parsed)
+    }
+
+    fn parse_name(&mut self) -> &'a str {
+        self.skip_whitespace();
+        let length = self
+            .rest
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(self.rest.len());
+        let (name, rest) = self.rest.split_at(length);
+        self.rest = rest;
+        name
+    }
+
+    fn parse_primary(&mut self) -> Result<Type, Error> {
+        let name = self.parse_name();
+        // The parameter of `opaque` is the name the host gave its handles
+        // rather than a type.
+        if name == "opaque" {
+            if !self.eat('<') {
+                return Ok(Type::Opaque(None));
+            }
+            let type_name = self.parse_name();
+            if type_name.is_empty() || !self.eat('>') {
+                return Err(self.error());
+            }
+            return Ok(Type::Opaque(Some(type_name.to_string())));
+        }
+        let element = if self.eat('<') {
+            let element = self.parse_union()?;
+            if !self.eat('>') {
+                return Err(self.error());
+            }
+            Some(Box::new(element))
+        } else {
+            None
+        };
+        let any = || Box::new(Type::Any);
+        match (name, element) {
+            ("any", None) => Ok(Type::Any),
+            ("null", None) => Ok(Type::Null),
+            ("bool", None) => Ok(Type::Bool),
+            ("number", None) => Ok(Type::Number),
+            ("string", None) => Ok(Type::String),
+            ("function", None) => Ok(Type::Function),
+            ("array", element) => Ok(Type::Array(element.unwrap_or_else(any))),
+            ("object", element) => Ok(Type::Object(element.unwrap_or_else(any))),
+            _ => Err(self.error()),
+        }
+    }
+}
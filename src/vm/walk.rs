@@ -0,0 +1,187 @@
+use super::*;
+
+// Applies a yapl function to the nodes of a tree. When `with_path` is set, the
+// function is called with `[node, path]`, where the path lists the keys and
+// indexes leading to the node from the root, rather than with the node alone.
+pub struct Walker<'a> {
+    pub env: &'a Arc<Env>,
+    pub function: &'a Function,
+    pub with_path: bool,
+}
+
+impl Walker<'_> {
+    fn apply(&self, value: Value, path: &[Value]) -> Result<Value, Error> {
+        if self.with_path {
+            let path = Value::Array(Arc::new(path.to_vec()));
+            self.function
+                .call(self.env, &Value::Array(Arc::new(vec![value, path])))
+        } else {
+            self.function.call(self.env, &value)
+        }
+    }
+}
+
+// Whether two values are the same node, comparing containers by identity so
+// that checking doesn't walk them.
+fn is_same(lhs: &Value, rhs: &Value) -> bool {
+    match (lhs, rhs) {
+        (Value::Array(lhs), Value::Array(rhs)) => Arc::ptr_eq(lhs, rhs),
+        (Value::Object(lhs), Value::Object(rhs)) => Arc::ptr_eq(lhs, rhs),
+        (Value::Function(lhs), Value::Function(rhs)) => Arc::ptr_eq(lhs, rhs),
+        (Value::Opaque(lhs), Value::Opaque(rhs)) => Arc::ptr_eq(lhs, rhs),
+        (Value::Array(_) | Value::Object(_) | Value::Function(_) | Value::Opaque(_), _) => false,
+        _ => lhs == rhs,
+    }
+}
+
+type Visit<'a> = dyn FnMut(&Value, &mut Vec<Value>) -> Result<Value, Error> + 'a;
+
+// Rebuilds `value` with `visit` applied to each of its children. When no child
+// changes, `value` itself is returned so unchanged subtrees stay shared.
+fn map_children(
+    env: &Env,
+    value: &Value,
+    path: &mut Vec<Value>,
+    visit: &mut Visit,
+) -> Result<Value, Error> {
+    match value {
+        Value::Array(values) => {
+            env.check_length(values.len())?;
+            let mut changed = false;
+            let mut results = Vec::with_capacity(values.len());
+            for (index, child) in values.iter().enumerate() {
+                path.push(Value::Number(index.into()));
+                let result = visit(child, path);
+                path.pop();
+                let result = result?;
+                changed |= !is_same(child, &result);
+                results.push(result);
+            }
+            Ok(if changed {
+                Value::Array(Arc::new(results))
+            } else {
+                value.clone()
+            })
+        }
+        Value::Object(object) => {
+            env.check_length(object.len())?;
+            let mut changed = false;
+            let mut results = ObjectMap::with_capacity(object.len());
+            for (key, child) in object.iter() {
+                path.push(Value::String(key.clone()));
+                let result = visit(child, path);
+                path.pop();
+                let result = result?;
+                changed |= !is_same(child, &result);
+                results.insert(key.clone(), result);
+            }
+            Ok(if changed {
+                Value::Object(Arc::new(results))
+            } else {
+                value.clone()
+            })
+        }
+        _ => Ok(value.clone()),
+    }
+}
+
+// Applies `inner` to each child of `value`, then `outer` to the rebuilt value.
+pub fn walk(inner: &Walker, outer: &Walker, value: &Value) -> Result<Value, Error> {
+    let mut path = Vec::new();
+    let value = map_children(inner.env, value, &mut path, &mut |child, path| {
+        inner.apply(child.clone(), path)
+    })?;
+    outer.apply(value, &path)
+}
+
+// Applies the walker to `value` and then to each child of the result, top down.
+pub fn prewalk(walker: &Walker, value: &Value) -> Result<Value, Error> {
+    fn visit(walker: &Walker, value: &Value, path: &mut Vec<Value>) -> Result<Value, Error> {
+        let value = walker.apply(value.clone(), path)?;
+        map_children(walker.env, &value, path, &mut |child, path| {
+            visit(walker, child, path)
+        })
+    }
+    visit(walker, value, &mut Vec::new())
+}
+
+// Applies the walker to each child of `value` and then to the rebuilt value,
+// bottom up.
+pub fn postwalk(walker: &Walker, value: &Value) -> Result<Value, Error> {
+    fn visit(walker: &Walker, value: &Value, path: &mut Vec<Value>) -> Result<Value, Error> {
+        let value = map_children(walker.env, value, path, &mut |child, path| {
+            visit(walker, child, path)
+        })?;
+        walker.apply(value, path)
+    }
+    visit(walker, value, &mut Vec::new())
+}
+
+// Returns `value` with the part at `path` replaced by `replace` applied to it,
+// or `None` if there is nothing at `path`. Paths are resolved as `query`'s
+// `select` resolves them.
+fn update_in(
+    value: &Value,
+    path: &[String],
+    replace: &mut dyn FnMut(&Value) -> Result<Value, Error>,
+) -> Result<Option<Value>, Error> {
+    let (segment, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return replace(value).map(Some),
+    };
+    match value {
+        Value::Object(object) => {
+            let key = match object.get_key_value(segment.as_str()) {
+                Some((key, _)) => key,
+                None if segment == "args" => match query::op_name(object) {
+                    Some(name) => object.get_key_value(name).unwrap().0,
+                    None => return Ok(None),
+                },
+                None => return Ok(None),
+            };
+            let child = match update_in(&object[key], rest, replace)? {
+                Some(child) => child,
+                None => return Ok(None),
+            };
+            let mut object = ObjectMap::clone(object);
+            object.insert(key.clone(), child);
+            Ok(Some(Value::Object(Arc::new(object))))
+        }
+        Value::Array(values) => {
+            let index = match segment.parse::<usize>() {
+                Ok(index) if index < values.len() => index,
+                _ => return Ok(None),
+            };
+            let child = match update_in(&values[index], rest, replace)? {
+                Some(child) => child,
+                None => return Ok(None),
+            };
+            let mut values = Vec::clone(values);
+            values[index] = child;
+            Ok(Some(Value::Array(Arc::new(values))))
+        }
+        _ => Ok(None),
+    }
+}
+
+// Replaces the part of every node matching `query` that the query selects with
+// the result of the walker, which is given the path of the matched node. Nodes
+// are visited bottom up, so a replacement is never visited again.
+pub fn replace_map(query: &Query, walker: &Walker, value: &Value) -> Result<Value, Error> {
+    fn visit(
+        query: &Query,
+        walker: &Walker,
+        value: &Value,
+        path: &mut Vec<Value>,
+    ) -> Result<Value, Error> {
+        let value = map_children(walker.env, value, path, &mut |child, path| {
+            visit(query, walker, child, path)
+        })?;
+        if !query.matches(&value, path) {
+            return Ok(value);
+        }
+        let mut replace = |selected: &Value| walker.apply(selected.clone(), path);
+        Ok(update_in(&value, query.selection(), &mut replace)?.unwrap_or(value))
+    }
+    visit(query, walker, value, &mut Vec::new())
+}
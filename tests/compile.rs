@@ -0,0 +1,70 @@
+extern crate jsonpl;
+
+use jsonpl::vm;
+
+fn run(source: &str) -> Result<vm::Value, vm::Error> {
+    vm::Engine::new().context("main.yapl")?.run(source)
+}
+
+#[test]
+fn programs_built_at_runtime_become_functions() {
+    let program = "
+let:
+  scale:
+    compile:
+      quasiquote:
+        lambda: [x]
+        +in: { +: [{ $: x }, { +: [{ $: offset }, { unquote: 10 }] }] }
+    +env: { export: { offset: 5 } }
++in: { scale: [1] }
+";
+    assert_eq!(run(program), vm::parse("16.0"));
+}
+
+#[test]
+fn stored_programs_load_as_functions() {
+    let mut engine = vm::Engine::new();
+    let stored = r#"{ "lambda": "name", "+in": ["Hello, ", { "$": "name" }] }"#;
+    engine.bind_value("stored", vm::parse(stored).unwrap());
+    let context = engine.context("main.yapl").unwrap();
+    let program = "let: { greet: { compile: { $: stored } } }\n+in: { greet: World }";
+    assert_eq!(context.run(program), vm::parse(r#"["Hello, ", World]"#));
+}
+
+#[test]
+fn programs_only_see_their_environment() {
+    let program = "
+let: { y: 3 }
++in:
+  let: { f: { compile: { quote: { lambda: x, +in: { $: y } } } } }
+  +in: 1
+";
+    assert_eq!(
+        run(program),
+        Err(vm::Error::InvalidProgram(
+            "main.yapl#/+in/$: Undefined symbol `y`".to_string()
+        ))
+    );
+}
+
+#[test]
+fn malformed_programs_fail_when_compiled() {
+    assert_eq!(
+        run("compile: { quote: { lambda: x } }"),
+        Err(vm::Error::InvalidProgram(
+            "main.yapl#: `lambda` requires `+in`".to_string()
+        ))
+    );
+    assert_eq!(
+        run("compile: { quote: 1 }"),
+        Err(vm::Error::InvalidType(
+            "Expected function, got number".to_string()
+        ))
+    );
+    assert_eq!(
+        run("compile: { quote: { lambda: x, +in: 1 } }\n+env: 1"),
+        Err(vm::Error::InvalidType(
+            "Expected object, got number".to_string()
+        ))
+    );
+}
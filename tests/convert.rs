@@ -0,0 +1,121 @@
+extern crate jsonpl;
+
+use jsonpl::vm;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Config {
+    name: String,
+    port: u16,
+    ratio: f64,
+    tags: Vec<String>,
+    owner: Option<String>,
+    mode: Mode,
+    shapes: Vec<Shape>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Mode {
+    Fast,
+    Safe,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Shape {
+    Point,
+    Circle(f64),
+    Line(i32, i32),
+    Rect { width: u32, height: u32 },
+}
+
+// Converts `value` to a `Value` and back, checking the `Value` is `expected`.
+fn round_trip<T>(value: &T, expected: &str)
+where
+    T: Serialize + serde::de::DeserializeOwned + PartialEq + Debug,
+{
+    let converted = vm::to_value(value).unwrap();
+    assert_eq!(Ok(converted.clone()), vm::parse(expected));
+    assert_eq!(&vm::from_value::<T>(&converted).unwrap(), value);
+}
+
+#[test]
+fn structs_and_enums_round_trip() {
+    let config = Config {
+        name: "server".to_string(),
+        port: 8080,
+        ratio: 0.5,
+        tags: vec!["a".to_string(), "b".to_string()],
+        owner: None,
+        mode: Mode::Safe,
+        shapes: vec![
+            Shape::Point,
+            Shape::Circle(1.5),
+            Shape::Line(-1, 2),
+            Shape::Rect {
+                width: 3,
+                height: 4,
+            },
+        ],
+    };
+    round_trip(
+        &config,
+        "
+name: server
+port: 8080
+ratio: 0.5
+tags: [a, b]
+owner: null
+mode: Safe
+shapes: [Point, { Circle: 1.5 }, { Line: [-1, 2] }, { Rect: { width: 3, height: 4 } }]
+",
+    );
+}
+
+#[test]
+fn options_round_trip() {
+    round_trip(&Some(1u8), "1");
+    round_trip(&None::<u8>, "null");
+    round_trip(&vec![Some("a".to_string()), None], "[a, null]");
+    // A missing field is `None`.
+    let config: Result<Config, _> = vm::from_value(
+        &vm::parse("{ name: n, port: 1, ratio: 1, tags: [], mode: Fast, shapes: [] }").unwrap(),
+    );
+    assert_eq!(config.unwrap().owner, None);
+}
+
+#[test]
+fn maps_with_integer_keys_round_trip() {
+    let map: BTreeMap<i32, String> = [(-1, "minus".to_string()), (2, "two".to_string())]
+        .into_iter()
+        .collect();
+    round_trip(&map, "{ '-1': minus, '2': two }");
+    let map: BTreeMap<u64, Vec<bool>> = [(7, vec![true])].into_iter().collect();
+    round_trip(&map, "{ '7': [true] }");
+}
+
+#[test]
+fn mismatched_values_are_errors() {
+    let value = vm::parse("{ port: eighty }").unwrap();
+    assert!(vm::from_value::<Config>(&value).is_err());
+    assert!(vm::from_value::<Mode>(&vm::parse("Slow").unwrap()).is_err());
+    assert!(vm::from_value::<BTreeMap<i32, bool>>(&vm::parse("{ a: true }").unwrap()).is_err());
+}
+
+#[test]
+fn non_finite_floats_do_not_convert() {
+    round_trip(&f64::MAX, "1.7976931348623157e308");
+    round_trip(&-0.25f32, "-0.25");
+    for (float, text) in [
+        (f64::NAN, "NaN"),
+        (f64::INFINITY, "inf"),
+        (f64::NEG_INFINITY, "-inf"),
+    ] {
+        let error = Err(vm::Error::InvalidNumber(text.to_string()));
+        assert_eq!(vm::to_value(&float), error);
+        assert_eq!(vm::to_value(&Shape::Circle(float)), error);
+        assert_eq!(vm::to_value(&(float as f32)), error);
+    }
+    assert!(vm::parse("[1, .nan]").is_err());
+}
@@ -0,0 +1,82 @@
+extern crate jsonpl;
+
+use jsonpl::vm;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[test]
+fn natives_may_be_closures_over_host_state() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut engine = vm::Engine::new();
+    let counter = calls.clone();
+    engine.bind_function("next", move |_env, _args| {
+        Ok(vm::Value::Number(
+            counter.fetch_add(1, Ordering::SeqCst).into(),
+        ))
+    });
+    let greeting = "Hello".to_string();
+    engine.bind_function("greet", move |_env, args| {
+        let name = vm::Value::as_str(args)?;
+        Ok(vm::Value::String(format!("{}, {}", greeting, name).into()))
+    });
+    let context = engine.context("main.yapl").unwrap();
+    assert_eq!(
+        context.run("[{ next: null }, { next: null }, { greet: World }]"),
+        vm::parse("[0, 1, 'Hello, World']")
+    );
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn contexts_can_be_reused() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut engine = vm::Engine::new();
+    let counter = calls.clone();
+    engine.bind_function("next", move |_env, _args| {
+        Ok(vm::Value::Number(
+            counter.fetch_add(1, Ordering::SeqCst).into(),
+        ))
+    });
+    engine.bind_value("limit", vm::parse("3").unwrap());
+    let context = engine.context("main.yapl").unwrap();
+    for expected in 0..3 {
+        assert_eq!(
+            context.run("[{ next: null }, { $: limit }]"),
+            vm::parse(&format!("[{}, 3]", expected))
+        );
+    }
+    // A second context of the same engine sees the same host bindings.
+    let other = engine.context("other.yapl").unwrap();
+    assert_eq!(other.run("next: null"), vm::parse("3"));
+}
+
+#[test]
+fn hosts_call_the_functions_programs_return() {
+    let context = vm::Engine::new().context("main.yapl").unwrap();
+    let add = context
+        .run("lambda: [a, b]\n+in: { +: [{ $: a }, { $: b }] }")
+        .unwrap();
+    assert_eq!(
+        context.call(&add, &vm::parse("[1, 2]").unwrap()),
+        vm::parse("3.0")
+    );
+    assert_eq!(
+        context.call(&add, &vm::parse("[1, 2]").unwrap()),
+        context.call(&add, &vm::parse("[2, 1]").unwrap())
+    );
+}
+
+#[test]
+fn hosts_bind_special_forms() {
+    let mut engine = vm::Engine::new();
+    // Returns its arguments unevaluated, along with the `+as` key.
+    engine.bind_special_form("raw", |_env, object, args| {
+        let label = object.get("+as").cloned().unwrap_or(vm::Value::Null);
+        Ok(vm::Value::Array(Arc::new(vec![args.clone(), label])))
+    });
+    let context = engine.context("main.yapl").unwrap();
+    assert_eq!(
+        context.run("raw: { $: undefined }\n+as: label"),
+        vm::parse("[{ $: undefined }, label]")
+    );
+}
@@ -0,0 +1,52 @@
+extern crate jsonpl;
+
+use jsonpl::vm;
+
+fn html(description: &str) -> Result<vm::Value, vm::Error> {
+    let engine = vm::Engine::new();
+    let program = format!("html:\n  quote:\n{}", description);
+    engine.context("main.yapl")?.run(&program)
+}
+
+fn rendered(html: &str) -> Result<vm::Value, vm::Error> {
+    Ok(vm::Value::String(vm::Str::from(html)))
+}
+
+#[test]
+fn text_and_attributes_are_escaped() {
+    let description =
+        "    tag: a\n    attributes: { href: \"/?a=1&b=\\\"2\\\"\" }\n    text: <script>";
+    assert_eq!(
+        html(description),
+        rendered("<a href=\"/?a=1&amp;b=&quot;2&quot;\">&lt;script&gt;</a>")
+    );
+}
+
+#[test]
+fn raw_text_elements_take_only_raw_content() {
+    let description = "    tag: script\n    text: { raw: \"if (a < b) go();\" }";
+    assert_eq!(
+        html(description),
+        rendered("<script>if (a < b) go();</script>")
+    );
+    let description = "    tag: script\n    text: \"alert(document.cookie)\"";
+    assert_eq!(
+        html(description),
+        Err(vm::Error::InvalidHtml(
+            "Content of <script> must be given as { raw: ... }".to_string()
+        ))
+    );
+    let description = "    tag: style\n    children: [{ raw: \"p {}\" }, \"</style><b>\"]";
+    assert!(matches!(html(description), Err(vm::Error::InvalidHtml(_))));
+}
+
+#[test]
+fn raw_text_cannot_close_its_element() {
+    let description = "    tag: script\n    text: { raw: \"</SCRIPT><b>\" }";
+    assert_eq!(
+        html(description),
+        Err(vm::Error::InvalidHtml(
+            "Content of <script> cannot contain </script".to_string()
+        ))
+    );
+}
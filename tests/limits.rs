@@ -0,0 +1,203 @@
+extern crate jsonpl;
+
+use jsonpl::vm;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Recurses forever, building nothing.
+const RECURSION: &str = "
+let:
+  loop:
+    lambda: [self, n]
+    +in: { self: [{ $: self }, { +: [{ $: n }, 1] }] }
++in: { loop: [{ $: loop }, 0] }
+";
+
+fn context(limits: vm::Limits) -> vm::Context {
+    let mut engine = vm::Engine::new();
+    let numbers = (0..1_000_000)
+        .map(|n| vm::Value::Number(n.into()))
+        .collect();
+    engine.bind_value("numbers", vm::Value::Array(Arc::new(numbers)));
+    engine.bind_function("nap", |_env, args| {
+        std::thread::sleep(Duration::from_millis(20));
+        Ok(args.clone())
+    });
+    // Checks the length of the array before building it.
+    engine.bind_function("range", |env, args| {
+        let length = vm::Value::as_number(args)?.as_u64().unwrap_or(0);
+        env.check_length(length as usize)?;
+        let values = (0..length).map(|n| vm::Value::Number(n.into())).collect();
+        Ok(vm::Value::Array(Arc::new(values)))
+    });
+    // Builds an array without checking its length.
+    engine.bind_function("zeros", |_env, args| {
+        let length = vm::Value::as_number(args)?.as_u64().unwrap_or(0);
+        let values = vec![vm::Value::Number(0.into()); length as usize];
+        Ok(vm::Value::Array(Arc::new(values)))
+    });
+    engine.bind_function("thread", |_env, _args| {
+        let thread = format!("{:?}", std::thread::current().id());
+        Ok(vm::Value::String(vm::Str::from(thread)))
+    });
+    engine.set_limits(limits);
+    engine.context("main.yapl").unwrap()
+}
+
+#[test]
+fn steps_are_limited() {
+    let context = context(vm::Limits {
+        max_steps: Some(1000),
+        ..Default::default()
+    });
+    let program = "import: { std/list: { names: [length] } }\n+in: { length: { $: numbers } }";
+    assert_eq!(
+        context.run(program),
+        Err(vm::Error::StepLimitExceeded(1000))
+    );
+    // Each run gets the whole budget.
+    assert_eq!(context.run("+: [1, 2]"), vm::parse("3.0"));
+    assert_eq!(
+        context.run(RECURSION),
+        Err(vm::Error::StepLimitExceeded(1000))
+    );
+}
+
+#[test]
+fn call_depth_is_limited() {
+    let context = context(vm::Limits {
+        max_call_depth: Some(50),
+        ..Default::default()
+    });
+    assert_eq!(
+        context.run(RECURSION),
+        Err(vm::Error::CallDepthExceeded(50))
+    );
+}
+
+#[test]
+fn call_depth_is_limited_by_default_when_anything_is_limited() {
+    let context = context(vm::Limits {
+        max_steps: Some(u64::MAX),
+        ..Default::default()
+    });
+    assert_eq!(
+        context.run(RECURSION),
+        Err(vm::Error::CallDepthExceeded(vm::DEFAULT_MAX_CALL_DEPTH))
+    );
+}
+
+#[test]
+fn deep_call_depths_fit_in_the_stack() {
+    let context = context(vm::Limits {
+        max_call_depth: Some(20_000),
+        ..Default::default()
+    });
+    assert_eq!(
+        context.run(RECURSION),
+        Err(vm::Error::CallDepthExceeded(20_000))
+    );
+    let function = context
+        .run("lambda: self\n+in: { self: { $: self } }")
+        .unwrap();
+    assert_eq!(
+        context.call(&function, &function),
+        Err(vm::Error::CallDepthExceeded(20_000))
+    );
+}
+
+#[test]
+fn collections_are_limited_where_they_are_built() {
+    let context = context(vm::Limits {
+        max_collection_size: Some(3),
+        ..Default::default()
+    });
+    let too_large = Err(vm::Error::CollectionSizeExceeded(3));
+    // Constants are part of the program rather than built by it.
+    assert_eq!(context.run("[1, 2, 3, 4]"), vm::parse("[1, 2, 3, 4]"));
+    assert_eq!(
+        context.run("[1, 2, { +: [1, 2] }]"),
+        vm::parse("[1, 2, 3.0]")
+    );
+    assert_eq!(context.run("[1, 2, 3, { +: [2, 2] }]"), too_large);
+    let object = "quasiquote: { a: 1, b: 2, c: 3, d: { unquote: { +: [2, 2] } } }";
+    assert_eq!(context.run(object), too_large);
+    assert_eq!(
+        context.run("eval: { quote: [1, 2, 3, { +: [2, 2] }] }"),
+        too_large
+    );
+    let splice =
+        "quasiquote: [{ unquote_splicing: { range: 2 } }, { unquote_splicing: { range: 2 } }]";
+    assert_eq!(context.run(splice), too_large);
+    // Strings are measured in bytes.
+    let program = "import: { std/string: { names: [concat] } }\n+in: { concat: [ab, cd] }";
+    assert_eq!(context.run(program), too_large);
+    let program = "import: { std/string: { names: [split] } }\n+in: { split: [\"\", ab] }";
+    assert_eq!(context.run(program), too_large);
+    // Natives check what they build before building it, and what they return
+    // is checked again.
+    assert_eq!(context.run("range: 1000000000000"), too_large);
+    assert_eq!(context.run("zeros: 4"), too_large);
+}
+
+#[test]
+fn runs_share_a_thread() {
+    let context = context(vm::Limits {
+        max_call_depth: Some(20_000),
+        ..Default::default()
+    });
+    let first = context.run("thread: null").unwrap();
+    assert_eq!(context.run("thread: null"), Ok(first.clone()));
+    let function = context.run("lambda: x\n+in: { thread: null }").unwrap();
+    assert_eq!(context.call(&function, &vm::Value::Null), Ok(first.clone()));
+    let current = format!("{:?}", std::thread::current().id());
+    assert_ne!(first, vm::Value::String(vm::Str::from(current)));
+}
+
+#[test]
+fn collections_built_by_recursion_are_limited() {
+    let context = context(vm::Limits {
+        max_collection_size: Some(1000),
+        ..Default::default()
+    });
+    let program = "
+let:
+  grow:
+    lambda: [self, xs]
+    +in:
+      self:
+        - { $: self }
+        - quasiquote: [{ unquote_splicing: { $: xs } }, { unquote_splicing: { $: xs } }]
++in: { grow: [{ $: grow }, [1]] }
+";
+    assert_eq!(
+        context.run(program),
+        Err(vm::Error::CollectionSizeExceeded(1000))
+    );
+}
+
+#[test]
+fn runs_are_limited_in_time() {
+    let context = context(vm::Limits {
+        timeout: Some(Duration::from_millis(50)),
+        ..Default::default()
+    });
+    let program = "
+import: { std/list: { names: [fold] } }
++in:
+  fold:
+    - lambda: [sum, n]
+      +in: { +: [{ $: sum }, { $: n }] }
+    - 0
+    - { $: numbers }
+";
+    assert_eq!(context.run(program), Err(vm::Error::DeadlineExceeded));
+    // Slow natives are checked even though they take few steps.
+    let start = Instant::now();
+    assert_eq!(
+        context.run("map: [{ $: nap }, [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]]"),
+        Err(vm::Error::DeadlineExceeded)
+    );
+    assert!(start.elapsed() < Duration::from_millis(200));
+    assert_eq!(context.run("nap: 1"), vm::parse("1"));
+}
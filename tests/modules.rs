@@ -0,0 +1,188 @@
+extern crate jsonpl;
+
+use jsonpl::vm;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+const FIXTURES: &str = "tests/modules";
+
+fn run(engine: &vm::Engine, source: &str) -> Result<vm::Value, vm::Error> {
+    let file = format!("{}/main.yapl", FIXTURES);
+    engine.context(&file)?.run(source)
+}
+
+#[test]
+fn circular_imports_are_reported() {
+    let err = run(&vm::Engine::new(), "import: { ./cycle_a: }\n+in: { $: a }").unwrap_err();
+    let chain = match err {
+        vm::Error::CircularImport(chain) => chain,
+        err => panic!("Expected a circular import, got {:?}", err),
+    };
+    let names: Vec<&str> = chain
+        .iter()
+        .map(|file| file.rsplit('/').next().unwrap())
+        .collect();
+    assert_eq!(names, ["cycle_a.yapl", "cycle_b.yapl", "cycle_a.yapl"]);
+}
+
+#[test]
+fn directories_resolve_to_their_index() {
+    let result = run(&vm::Engine::new(), "import: { ./pkg: }\n+in: { $: name }");
+    assert_eq!(result, vm::parse("pkg"));
+}
+
+#[test]
+fn search_path_is_searched_in_order_before_the_program_directory() {
+    let program = "import: { shared: , local: }\n+in: [{ $: shared }, { $: local }]";
+    let mut engine = vm::Engine::new();
+    assert_eq!(run(&engine, program), vm::parse("[root, root]"));
+    engine.search_path = vec![
+        PathBuf::from(FIXTURES).join("first"),
+        PathBuf::from(FIXTURES).join("second"),
+    ];
+    assert_eq!(run(&engine, program), vm::parse("[first, root]"));
+    engine.search_path.reverse();
+    assert_eq!(run(&engine, program), vm::parse("[second, root]"));
+    // Relative names only look next to the importing file.
+    let program = "import: { ./shared: }\n+in: { $: shared }";
+    assert_eq!(run(&engine, program), vm::parse("root"));
+}
+
+#[test]
+fn modules_are_cached_until_they_change() {
+    let directory = std::env::temp_dir().join(format!("yapl-cache-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let module = directory.join("counted.yapl");
+    fs::write(&module, "export: { value: 1, loads: { load: null } }").unwrap();
+
+    let loads = Arc::new(AtomicUsize::new(0));
+    let mut engine = vm::Engine::new();
+    let counter = loads.clone();
+    engine.bind_function("load", move |_env, _args| {
+        Ok(vm::Value::Number(
+            counter.fetch_add(1, Ordering::SeqCst).into(),
+        ))
+    });
+    let context = engine
+        .context(directory.join("main.yapl").to_str().unwrap())
+        .unwrap();
+    let program = "
+import: { ./counted: { as: first } }
++in:
+  import: { ./counted: { as: second } }
+  +in: [{ $: first }, { $: second }]
+";
+    let expected = vm::parse("[{ value: 1, loads: 0 }, { value: 1, loads: 0 }]");
+    assert_eq!(context.run(program), expected);
+    assert_eq!(context.run(program), expected);
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+    fs::write(&module, "export: { value: 2, loads: { load: null } }").unwrap();
+    let later = SystemTime::now() + Duration::from_secs(10);
+    fs::File::options()
+        .write(true)
+        .open(&module)
+        .unwrap()
+        .set_modified(later)
+        .unwrap();
+    let expected = vm::parse("[{ value: 2, loads: 1 }, { value: 2, loads: 1 }]");
+    assert_eq!(context.run(program), expected);
+    assert_eq!(loads.load(Ordering::SeqCst), 2);
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn json_is_read_as_json() {
+    let engine = vm::Engine::new();
+    let result = run(
+        &engine,
+        "import: { ./data.json: { as: data } }\n+in: { $: data }",
+    );
+    assert_eq!(
+        vm::serialize(&result.unwrap()).unwrap(),
+        r#"{"zebra":1,"apple":[true,null,1.5]}"#
+    );
+    // Unquoted keys are valid YAML but not JSON.
+    let result = run(
+        &engine,
+        "import: { ./invalid.json: { as: data } }\n+in: { $: data }",
+    );
+    assert_eq!(
+        result,
+        Err(vm::Error::InvalidData(
+            "key must be a string at line 1 column 3".to_string()
+        ))
+    );
+}
+
+#[test]
+fn imports_shadow_the_prelude_but_not_the_program() {
+    let mut engine = vm::Engine::new();
+    engine.prelude = true;
+    let result = run(
+        &engine,
+        "import: { std/list: }\n+in: { reverse: { quote: [1, 2] } }",
+    );
+    assert_eq!(result, vm::parse("[2, 1]"));
+    let program = "let: { map: 1 }\n+in:\n  import: { ./exports_map: }\n  +in: { $: map }";
+    assert_eq!(
+        run(&engine, program),
+        Err(vm::Error::NameCollision("map".to_string()))
+    );
+}
+
+#[test]
+fn special_forms_in_an_import_compile_once() {
+    let engine = vm::Engine::new();
+    let compiled_forms = |calls: usize| {
+        let calls = vec!["{ f: [true] }"; calls].join(", ");
+        let program = format!(
+            "let:\n  f:\n    lambda: [x]\n    +in:\n      import: {{ ./exports_map: }}\n      +in: {{ if: {{ $: x }}, +then: 1, +else: 2 }}\n+in: [{}]",
+            calls
+        );
+        let before = vm::compiled_forms();
+        let result = run(&engine, &program).unwrap();
+        assert!(matches!(result, vm::Value::Array(_)));
+        vm::compiled_forms() - before
+    };
+    assert_eq!(compiled_forms(1), compiled_forms(100));
+}
+
+#[test]
+fn the_checker_lets_imports_shadow_what_the_program_does_not_bind() {
+    let mut engine = vm::Engine::new();
+    engine.prelude = true;
+    let context = engine.context(&format!("{}/main.yapl", FIXTURES)).unwrap();
+    let check = |source: &str| {
+        let problems = context.check(&vm::parse(source).unwrap());
+        problems
+            .iter()
+            .map(|problem| format!("#{}: {}", problem.path, problem.message))
+            .collect::<Vec<String>>()
+    };
+    let program = "import: { ./exports_map: }\n+in: { println: { map: [1, 2] } }";
+    assert_eq!(check(program), Vec::<String>::new());
+    assert_eq!(run(&engine, program), vm::parse("null"));
+    let program = "import: { std/list: }\n+in: { reverse: { quote: [1, 2] } }";
+    assert_eq!(check(program), Vec::<String>::new());
+    let program = "let: { map: 1 }\n+in:\n  import: { ./exports_map: }\n  +in: { $: map }";
+    assert_eq!(check(program), ["#/+in/import: `map` is already bound"]);
+    // The `compile` form checks the programs it compiles the same way.
+    let program = "
+compile:
+  quote:
+    lambda: x
+    +in:
+      import: { ./exports_map: }
+      +in: { map: [{ $: x }, []] }
+";
+    let function = run(&engine, program).unwrap();
+    let context = engine.context(&format!("{}/main.yapl", FIXTURES)).unwrap();
+    assert_eq!(
+        context.call(&function, &vm::parse("1").unwrap()),
+        vm::parse("shadowed")
+    );
+}
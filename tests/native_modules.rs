@@ -0,0 +1,68 @@
+extern crate jsonpl;
+
+use jsonpl::vm;
+use std::fs;
+
+fn greeter() -> vm::NativeModule {
+    let mut module = vm::NativeModule::new();
+    module.bind_function("greet", |_env, args| {
+        let name = vm::Value::as_str(args)?;
+        Ok(vm::Value::String(format!("Hello, {}", name).into()))
+    });
+    module.bind_value("shared", vm::parse("native").unwrap());
+    module
+}
+
+fn run(engine: &vm::Engine, source: &str) -> Result<vm::Value, vm::Error> {
+    engine.context("main.yapl")?.run(source)
+}
+
+#[test]
+fn native_modules_are_imported_by_name() {
+    let mut engine = vm::Engine::new();
+    engine.register_module("greeter", greeter());
+    let program = "import: { greeter: }\n+in: [{ greet: World }, { $: shared }]";
+    assert_eq!(run(&engine, program), vm::parse("['Hello, World', native]"));
+    let program = "import: { greeter: { as: g, names: [shared] } }\n+in: { $: g }";
+    assert_eq!(run(&engine, program), vm::parse("{ shared: native }"));
+    assert_eq!(
+        run(
+            &engine,
+            "import: { greeter: { names: [missing] } }\n+in: null"
+        ),
+        Err(vm::Error::MissingExport(
+            "greeter".to_string(),
+            "missing".to_string()
+        ))
+    );
+    assert!(matches!(
+        run(&engine, "import: { greeter: { format: json } }\n+in: null"),
+        Err(vm::Error::InvalidOperation(_))
+    ));
+}
+
+#[test]
+fn native_modules_take_precedence_over_files() {
+    let directory = std::env::temp_dir().join(format!("yapl-native-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join("shared.yapl"), "export: { shared: file }").unwrap();
+    let file = directory.join("main.yapl");
+    let program = "import: { shared: { names: [shared] } }\n+in: { $: shared }";
+    let mut engine = vm::Engine::new();
+    let run = |engine: &vm::Engine| engine.context(file.to_str().unwrap())?.run(program);
+    assert_eq!(run(&engine), vm::parse("file"));
+    engine.register_module("shared", greeter());
+    assert_eq!(run(&engine), vm::parse("native"));
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn native_module_exports_are_checked() {
+    let mut engine = vm::Engine::new();
+    engine.register_module("greeter", greeter());
+    let context = engine.context("main.yapl").unwrap();
+    let program = vm::parse("import: { greeter: }\n+in: { greet: World }").unwrap();
+    assert_eq!(context.check(&program), Vec::new());
+    let program = vm::parse("import: { greeter: }\n+in: { wave: World }").unwrap();
+    assert_eq!(context.check(&program).len(), 1);
+}
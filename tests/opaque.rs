@@ -0,0 +1,139 @@
+extern crate jsonpl;
+
+use jsonpl::vm;
+use std::sync::Arc;
+
+#[derive(Debug, PartialEq)]
+struct Counter(u32);
+
+// A handle whose host name is that of a builtin type.
+fn handle() -> vm::Value {
+    vm::Value::Opaque(Arc::new(vm::Opaque::new("string", Arc::new(Counter(7)))))
+}
+
+#[test]
+fn handles_downcast_to_their_resource() {
+    let value = handle();
+    let opaque = vm::Value::as_opaque(&value).unwrap();
+    assert_eq!(opaque.downcast_ref::<Counter>(), Ok(&Counter(7)));
+    let shared = opaque.downcast::<Counter>().unwrap();
+    assert!(std::ptr::eq(
+        shared.as_ref(),
+        opaque.downcast_ref::<Counter>().unwrap()
+    ));
+    assert_eq!(
+        opaque.downcast_ref::<String>(),
+        Err(vm::Error::InvalidType(
+            "Expected alloc::string::String, got opaque<string>".to_string()
+        ))
+    );
+    assert!(opaque.downcast::<u32>().is_err());
+}
+
+#[test]
+fn handles_serialize_as_their_placeholder() {
+    let opaque = vm::Opaque::new("counter", Arc::new(Counter(1))).with_placeholder("#counter");
+    let value = vm::Value::Array(Arc::new(vec![vm::Value::Opaque(Arc::new(opaque))]));
+    assert_eq!(vm::serialize(&value), Ok(r##"["#counter"]"##.to_string()));
+}
+
+#[test]
+fn handles_without_a_placeholder_fail_to_serialize() {
+    let value = handle();
+    assert_eq!(vm::serialize(&value), Err(vm::Error::Serialization));
+    let err = serde_json::to_string(&value).unwrap_err();
+    assert_eq!(err.to_string(), "Cannot serialize opaque<string>");
+    assert!(vm::from_value::<String>(&value).is_err());
+}
+
+#[test]
+fn handles_are_opaque_whatever_the_host_calls_them() {
+    let value = handle();
+    assert_eq!(value.type_of(), "opaque");
+    assert_eq!(value.type_description(), "opaque<string>");
+
+    let mut engine = vm::Engine::new();
+    engine.bind_value("handle", value.clone());
+    let context = engine.context("main.yapl").unwrap();
+    let program = "
+case: { $: handle }
++of:
+  - +pattern: { +type: string }
+    +then: string
+  - +pattern: { +type: opaque }
+    +then: opaque
+";
+    assert_eq!(context.run(program), vm::parse("opaque"));
+    let document = vm::Value::Array(Arc::new(vec![value.clone()]));
+    let query = |types: &str| {
+        let query = vm::parse(&format!("{{ where: {{ type: {} }} }}", types)).unwrap();
+        vm::Query::parse(&query).unwrap().run(&document)
+    };
+    assert_eq!(query("opaque"), vec![value]);
+    assert_eq!(query("string"), vec![]);
+}
+
+#[test]
+fn messages_name_handles_as_the_host_does() {
+    let mut engine = vm::Engine::new();
+    engine.bind_typed_function("negate", |value: bool| !value);
+    let regex = vm::Opaque::new("regex", Arc::new(Counter(1)));
+    engine.bind_value("regex", vm::Value::Opaque(Arc::new(regex)));
+    let context = engine.context("main.yapl").unwrap();
+    assert_eq!(
+        context.run("negate: { $: regex }"),
+        Err(vm::Error::InvalidArgument(
+            "negate".to_string(),
+            "Expected bool, got opaque<regex>".to_string()
+        ))
+    );
+}
+
+#[test]
+fn types_match_handles_by_their_host_name() {
+    let regex = vm::Value::Opaque(Arc::new(vm::Opaque::new("regex", Arc::new(Counter(1)))));
+    for (source, expected) in [
+        ("opaque", None),
+        ("opaque<regex>", None),
+        ("opaque<regex> | string", None),
+        (
+            "opaque<file>",
+            Some("expected opaque<file> at `x`, got opaque<regex>"),
+        ),
+        ("string", Some("expected string at `x`, got opaque<regex>")),
+    ] {
+        let parsed = vm::Type::parse(&vm::Value::String(vm::Str::from(source))).unwrap();
+        assert_eq!(parsed.to_string(), source);
+        assert_eq!(parsed.check(&regex, "x").as_deref(), expected, "{}", source);
+    }
+    for source in ["opaque<>", "opaque<regex", "opaque<array<string>>"] {
+        assert!(vm::Type::parse(&vm::Value::String(vm::Str::from(source))).is_err());
+    }
+
+    let mut engine = vm::Engine::new();
+    engine.bind_value("regex", regex);
+    let context = engine.context("main.yapl").unwrap();
+    let program = "
+let:
+  name:
+    lambda: [handle]
+    +types: { handle: opaque<regex> }
+    +in: regex
++in: { name: [{ $: regex }] }
+";
+    assert_eq!(context.run(program), vm::parse("regex"));
+    let program = "
+let:
+  name:
+    lambda: [handle]
+    +types: { handle: opaque<file> }
+    +in: file
++in: { name: [{ $: regex }] }
+";
+    assert_eq!(
+        context.run(program),
+        Err(vm::Error::InvalidType(
+            "Parameter `handle`: expected opaque<file> at `handle`, got opaque<regex>".to_string()
+        ))
+    );
+}
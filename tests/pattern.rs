@@ -0,0 +1,97 @@
+extern crate jsonpl;
+
+use jsonpl::vm;
+
+#[test]
+fn repeated_binders_are_rejected() {
+    let repeated = vm::Error::InvalidPattern("`a` is bound more than once".to_string());
+    for source in ["[$a, $a]", "{ x: $a, +as: a }", "[$a, ...$a]"] {
+        let pattern = vm::parse(source).unwrap();
+        assert_eq!(
+            vm::Pattern::parse(&pattern),
+            Err(repeated.clone()),
+            "{}",
+            source
+        );
+    }
+    let pattern = vm::parse("{ x: a, y: [b, a] }").unwrap();
+    assert_eq!(vm::Pattern::parse_binding(&pattern), Err(repeated));
+}
+
+#[test]
+fn repeated_binders_are_rejected_by_case() {
+    let env = vm::Env::builtin("test.yapl".to_string());
+    let program = vm::parse("case: [1, 2]\n+of: [{ +pattern: [$a, $a], +then: 1 }]").unwrap();
+    assert!(matches!(
+        vm::eval(&env, &program),
+        Err(vm::Error::InvalidPattern(_))
+    ));
+}
+
+fn run(source: &str) -> Result<vm::Value, vm::Error> {
+    vm::Engine::new().context("main.yapl")?.run(source)
+}
+
+#[test]
+fn let_destructures_values_with_binding_patterns() {
+    let bindings = "
+let:
+  account:
+    +pattern: { user: { name, email: address }, tags: [first, ...rest] }
+    +value:
+      quote: { user: { name: Ada, email: ada@example.com }, tags: [a, b, c] }
+";
+    assert_eq!(
+        run(&format!(
+            "{}+in: [{{ $: name }}, {{ $: address }}, {{ $: first }}, {{ $: rest }}]",
+            bindings
+        )),
+        vm::parse("[Ada, ada@example.com, a, [b, c]]")
+    );
+    // The key still binds the whole value.
+    assert_eq!(
+        run(&format!("{}+in: {{ $: account }}", bindings)),
+        vm::parse("{ user: { name: Ada, email: ada@example.com }, tags: [a, b, c] }")
+    );
+    let program =
+        "let: { a: { +pattern: { user: { name } }, +value: { quote: { user: {} } } } }\n+in: 1";
+    assert_eq!(
+        run(program),
+        Err(vm::Error::MissingPath("user.name".to_string()))
+    );
+}
+
+#[test]
+fn malformed_let_patterns_are_reported() {
+    let context = vm::Engine::new().context("main.yapl").unwrap();
+    for (bindings, error) in [
+        (
+            "{ +pattern: 1, +value: 2 }",
+            vm::Error::InvalidType("Expected binding pattern, got number".to_string()),
+        ),
+        (
+            "{ +pattern: [x, x], +value: [1, 2] }",
+            vm::Error::InvalidPattern("`x` is bound more than once".to_string()),
+        ),
+        (
+            "{ +pattern: [x] }",
+            vm::Error::UnknownKey("+value".to_string()),
+        ),
+        (
+            "{ +pattern: [x], +value: [1], +as: y }",
+            vm::Error::InvalidPattern("Unknown key: +as".to_string()),
+        ),
+    ] {
+        let program = format!("let: {{ a: {} }}\n+in: 1", bindings);
+        assert_eq!(context.run(&program), Err(error.clone()), "{}", bindings);
+        let problems: Vec<String> = context
+            .check(&vm::parse(&program).unwrap())
+            .iter()
+            .map(|problem| problem.to_string())
+            .collect();
+        assert_eq!(
+            problems,
+            [format!("main.yapl#/let/a: Invalid pattern: {:?}", error)]
+        );
+    }
+}
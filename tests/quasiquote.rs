@@ -0,0 +1,69 @@
+extern crate jsonpl;
+
+use jsonpl::vm;
+
+fn run(source: &str) -> Result<vm::Value, vm::Error> {
+    vm::Engine::new().context("main.yapl")?.run(source)
+}
+
+#[test]
+fn holes_are_filled_at_any_depth() {
+    let program = "
+let: { name: greet, args: [1, 2], extra: [3] }
++in:
+  quasiquote:
+    lambda: x
+    +in:
+      - { unquote: { $: name } }
+      - a: { b: [{ unquote: { $: args } }, { unquote_splicing: { $: args } }] }
+      - [0, { unquote_splicing: { $: extra } }, { unquote_splicing: [] }, 4]
+";
+    assert_eq!(
+        run(program),
+        vm::parse("{ lambda: x, +in: [greet, { a: { b: [[1, 2], 1, 2] } }, [0, 3, 4]] }")
+    );
+}
+
+#[test]
+fn nested_templates_keep_their_own_holes() {
+    let program = "
+let: { x: 1 }
++in:
+  quasiquote:
+    quasiquote:
+      - { unquote: { $: y } }
+      - { unquote: { unquote: { $: x } } }
+";
+    assert_eq!(
+        run(program),
+        vm::parse("{ quasiquote: [{ unquote: { $: y } }, { unquote: 1 }] }")
+    );
+}
+
+#[test]
+fn built_code_runs() {
+    let program = "
+let: { n: 41 }
++in:
+  eval:
+    quasiquote:
+      +: [{ unquote: { $: n } }, 1]
+";
+    assert_eq!(run(program), vm::parse("42.0"));
+}
+
+#[test]
+fn splices_must_be_arrays_in_arrays() {
+    assert_eq!(
+        run("let: { x: 1 }\n+in:\n  quasiquote: [{ unquote_splicing: { $: x } }]"),
+        Err(vm::Error::InvalidType(
+            "Expected array, got number".to_string()
+        ))
+    );
+    assert_eq!(
+        run("quasiquote: { a: { unquote_splicing: [1] } }"),
+        Err(vm::Error::InvalidOperation(
+            "unquote_splicing must be an element of an array".to_string()
+        ))
+    );
+}
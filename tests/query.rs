@@ -0,0 +1,20 @@
+extern crate jsonpl;
+
+use jsonpl::vm;
+
+#[test]
+fn consecutive_wildcards_match_deep_documents() {
+    let mut document = vm::parse("{ id: 1 }").unwrap();
+    for _ in 0..40 {
+        document = vm::to_value(&[document]).unwrap();
+    }
+    let query = vm::parse(
+        "{ where: { key: id, path: '**.**.**.**.**.**.**.**.**.**.**.**.**.**.**.**.id' } }",
+    );
+    let query = vm::Query::parse(&query.unwrap()).unwrap();
+    assert_eq!(query.run(&document), vec![vm::Value::Number(1.into())]);
+    let query =
+        vm::parse("{ where: { path: '**.**.**.**.**.**.**.**.**.**.**.**.**.**.**.**.missing' } }");
+    let query = vm::Query::parse(&query.unwrap()).unwrap();
+    assert_eq!(query.run(&document), vec![]);
+}
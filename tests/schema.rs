@@ -0,0 +1,46 @@
+extern crate jsonpl;
+
+use jsonpl::vm;
+
+fn validate(schema: &str, instance: &str) -> Result<Vec<vm::ValidationError>, vm::Error> {
+    vm::validate(&vm::parse(schema).unwrap(), &vm::parse(instance).unwrap())
+}
+
+#[test]
+fn types_are_json_schema_types() {
+    assert_eq!(validate("{ type: boolean }", "true").unwrap(), vec![]);
+    assert_eq!(validate("{ type: integer }", "2.0").unwrap(), vec![]);
+    let errors = validate("{ type: ['null', string] }", "false").unwrap();
+    assert_eq!(errors[0].message, "Expected null or string, got boolean");
+    for name in ["bool", "function"] {
+        let schema = format!("{{ type: {} }}", name);
+        assert_eq!(
+            validate(&schema, "true"),
+            Err(vm::Error::InvalidSchema(format!(
+                "Unknown type `{}` (at #/type)",
+                name
+            )))
+        );
+    }
+}
+
+#[test]
+fn unsupported_keywords_are_reported() {
+    let schemas = [
+        ("{ $dynamicRef: '#node' }", "/$dynamicRef"),
+        (
+            "{ items: { unevaluatedItems: false } }",
+            "/items/unevaluatedItems",
+        ),
+        ("{ unevaluatedProperties: false }", "/unevaluatedProperties"),
+    ];
+    for (schema, path) in schemas {
+        assert_eq!(
+            validate(schema, "[{}]"),
+            Err(vm::Error::InvalidSchema(format!(
+                "Unsupported keyword (at #{})",
+                path
+            )))
+        );
+    }
+}
@@ -0,0 +1,111 @@
+extern crate jsonpl;
+
+use jsonpl::vm;
+use vm::{Shape, Str};
+
+fn engine() -> vm::Engine {
+    let mut engine = vm::Engine::new();
+    engine.bind_function_with_shape(
+        "pair",
+        Shape::Positional { min: 1, max: 2 },
+        |_env, args| Ok(args.clone()),
+    );
+    engine.bind_function_with_shape(
+        "greet",
+        Shape::Named {
+            required: vec![Str::key("name")],
+            optional: vec![Str::key("greeting")],
+        },
+        |_env, args| Ok(args.clone()),
+    );
+    engine
+}
+
+fn shape_of(context: &vm::Context, source: &str) -> Option<Shape> {
+    let function = context.run(source).unwrap();
+    vm::Value::as_function(&function).unwrap().shape()
+}
+
+fn invalid_argument(name: &str, message: &str) -> Result<vm::Value, vm::Error> {
+    Err(vm::Error::InvalidArgument(
+        name.to_string(),
+        message.to_string(),
+    ))
+}
+
+#[test]
+fn functions_report_their_shape() {
+    let context = engine().context("main.yapl").unwrap();
+    assert_eq!(
+        shape_of(&context, "$: pair"),
+        Some(Shape::Positional { min: 1, max: 2 })
+    );
+    assert_eq!(shape_of(&context, "$: println"), Some(Shape::Singleton));
+    assert_eq!(
+        shape_of(&context, "lambda: [a, ...rest]\n+in: null"),
+        Some(Shape::Positional {
+            min: 1,
+            max: usize::MAX
+        })
+    );
+    assert_eq!(
+        shape_of(&context, "lambda: { a, b }\n+in: null"),
+        Some(Shape::Named {
+            required: vec![Str::key("a"), Str::key("b")],
+            optional: Vec::new(),
+        })
+    );
+    assert_eq!(
+        shape_of(&context, "lambda: x\n+in: null"),
+        Some(Shape::Singleton)
+    );
+    assert_eq!(shape_of(&context, "$: if"), None);
+}
+
+#[test]
+fn arguments_are_checked_against_the_shape() {
+    let context = engine().context("main.yapl").unwrap();
+    assert_eq!(context.run("pair: [1, 2]"), vm::parse("[1, 2]"));
+    assert_eq!(
+        context.run("pair: []"),
+        invalid_argument("pair", "Expected 1 to 2 arguments, got 0")
+    );
+    assert_eq!(
+        context.run("pair: [1, 2, 3]"),
+        invalid_argument("pair", "Expected 1 to 2 arguments, got 3")
+    );
+    assert!(matches!(
+        context.run("pair: 1"),
+        Err(vm::Error::InvalidArgument(name, _)) if name == "pair"
+    ));
+    assert_eq!(
+        context.run("greet: { name: World }"),
+        vm::parse("{ name: World }")
+    );
+    assert_eq!(
+        context.run("greet: { greeting: Hi }"),
+        invalid_argument("greet", "Missing named argument `name`")
+    );
+    assert_eq!(
+        context.run("greet: [World]"),
+        invalid_argument("greet", "Expected named arguments, got array")
+    );
+}
+
+#[test]
+fn calls_from_map_and_hosts_are_checked() {
+    let context = engine().context("main.yapl").unwrap();
+    assert_eq!(
+        context.run("map: [{ $: pair }, [[1], [2, 3]]]"),
+        vm::parse("[[1], [2, 3]]")
+    );
+    assert_eq!(
+        context.run("map: [{ $: pair }, [[1], []]]"),
+        invalid_argument("pair", "Expected 1 to 2 arguments, got 0")
+    );
+    let greet = context.run("$: greet").unwrap();
+    assert_eq!(
+        context.call(&greet, &vm::parse("{ greeting: Hi }").unwrap()),
+        invalid_argument("greet", "Missing named argument `name`")
+    );
+}
@@ -0,0 +1,56 @@
+extern crate jsonpl;
+
+use jsonpl::vm;
+use std::sync::Arc;
+
+const MODULES: [&str; 5] = ["std", "std/list", "std/prelude", "std/string", "std/test"];
+
+#[test]
+fn standard_modules_check_cleanly() {
+    let engine = vm::Engine::new();
+    for module in MODULES {
+        assert_eq!(engine.check_file(module), Ok(Vec::new()), "{}", module);
+    }
+}
+
+#[test]
+fn folds_iterate_over_long_lists() {
+    let mut engine = vm::Engine::new();
+    let numbers = (0..100_000).map(|n| vm::Value::Number(n.into())).collect();
+    engine.bind_value("numbers", vm::Value::Array(Arc::new(numbers)));
+    let context = engine.context("main.yapl").unwrap();
+    let program = "
+import: { std/list: { names: [fold, length] } }
++in:
+  - length: { $: numbers }
+  - fold:
+      - lambda: [sum, n]
+        +in: { +: [{ $: sum }, { $: n }] }
+      - 0
+      - { $: numbers }
+";
+    assert_eq!(context.run(program), vm::parse("[100000.0, 4999950000.0]"));
+}
+
+#[test]
+fn reverse_and_filter_handle_long_lists() {
+    let mut engine = vm::Engine::new();
+    let numbers: Vec<vm::Value> = (0..100_000).map(|n| vm::Value::Number(n.into())).collect();
+    engine.bind_value("numbers", vm::Value::Array(Arc::new(numbers.clone())));
+    let context = engine.context("main.yapl").unwrap();
+    let program = "
+import: { std/list: { names: [filter, reverse] } }
++in:
+  - reverse: { $: numbers }
+  - filter:
+      - lambda: n
+        +in: { eq: [{ $: n }, 99998] }
+      - { $: numbers }
+";
+    let reversed = numbers.into_iter().rev().collect();
+    let expected = vm::Value::Array(Arc::new(vec![
+        vm::Value::Array(Arc::new(reversed)),
+        vm::parse("[99998]").unwrap(),
+    ]));
+    assert_eq!(context.run(program), Ok(expected));
+}
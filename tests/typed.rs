@@ -0,0 +1,86 @@
+extern crate jsonpl;
+
+use jsonpl::vm;
+use serde::Deserialize;
+use vm::{Shape, Str};
+
+#[derive(Debug, Deserialize)]
+struct Greeting {
+    name: String,
+    greeting: Option<String>,
+    times: Option<u8>,
+}
+
+fn greet(vm::Named(args): vm::Named<Greeting>) -> String {
+    let greeting = args.greeting.unwrap_or_else(|| "Hello".to_string());
+    format!("{}, {}", greeting, args.name).repeat(args.times.unwrap_or(1).into())
+}
+
+fn engine() -> vm::Engine {
+    let mut engine = vm::Engine::new();
+    engine.bind_typed_function("repeat", |text: Str, times: Option<usize>| {
+        text.repeat(times.unwrap_or(2))
+    });
+    engine.bind_typed_function("negate", |n: i8| -n);
+    engine.bind_typed_function("greet", greet);
+    engine
+}
+
+fn invalid_argument(name: &str, message: &str) -> Result<vm::Value, vm::Error> {
+    Err(vm::Error::InvalidArgument(
+        name.to_string(),
+        message.to_string(),
+    ))
+}
+
+#[test]
+fn integers_accept_integral_floats() {
+    let context = engine().context("main.yapl").unwrap();
+    assert_eq!(
+        context.run("repeat: [ab, { +: [1, 1] }]"),
+        vm::parse("abab")
+    );
+    assert_eq!(context.run("negate: 3.0"), vm::parse("-3"));
+    assert_eq!(context.run("negate: -0.0"), vm::parse("0"));
+    assert_eq!(
+        context.run("repeat: [ab, 1.5]"),
+        invalid_argument("repeat", "Argument 2: Invalid number 1.5")
+    );
+    assert_eq!(
+        context.run("repeat: [ab, -2.0]"),
+        invalid_argument("repeat", "Argument 2: Invalid number -2.0")
+    );
+    assert_eq!(
+        context.run("negate: 1000.0"),
+        invalid_argument("negate", "Invalid number 1000.0")
+    );
+    assert_eq!(
+        context.run("negate: 1e300"),
+        invalid_argument("negate", "Invalid number 1e+300")
+    );
+}
+
+#[test]
+fn named_fields_are_optional_when_they_are_options() {
+    let context = engine().context("main.yapl").unwrap();
+    let greet = context.run("$: greet").unwrap();
+    assert_eq!(
+        vm::Value::as_function(&greet).unwrap().shape(),
+        Some(Shape::Named {
+            required: vec![Str::key("name")],
+            optional: vec![Str::key("greeting"), Str::key("times")],
+        })
+    );
+    assert_eq!(
+        context.run("greet: { name: World }"),
+        vm::parse("'Hello, World'")
+    );
+    assert_eq!(
+        context.run("greet: { name: World, greeting: Hi, times: 2 }"),
+        vm::parse("'Hi, WorldHi, World'")
+    );
+    assert_eq!(
+        context.run("greet: { greeting: Hi }"),
+        invalid_argument("greet", "Missing named argument `name`")
+    );
+}
@@ -0,0 +1,141 @@
+extern crate jsonpl;
+
+use jsonpl::vm;
+use std::sync::{Arc, Mutex};
+
+// Runs `program` with `tree` bound, and with a `visit` native that records the
+// nodes it is given and returns them unchanged.
+fn run_with_visits(program: &str, tree: &str) -> (Result<vm::Value, vm::Error>, Vec<String>) {
+    let visits = Arc::new(Mutex::new(Vec::new()));
+    let mut engine = vm::Engine::new();
+    engine.bind_value("tree", vm::parse(tree).unwrap());
+    let recorded = visits.clone();
+    engine.bind_function("visit", move |_env, node| {
+        recorded.lock().unwrap().push(vm::serialize(node)?);
+        Ok(node.clone())
+    });
+    let result = engine.context("main.yapl").unwrap().run(program);
+    let visits = visits.lock().unwrap().clone();
+    (result, visits)
+}
+
+#[test]
+fn prewalk_visits_parents_before_children() {
+    let (result, visits) = run_with_visits(
+        "prewalk: [{ $: visit }, { $: tree }]",
+        "{ a: [1, 2], b: 3 }",
+    );
+    assert_eq!(result, vm::parse("{ a: [1, 2], b: 3 }"));
+    assert_eq!(visits, [r#"{"a":[1,2],"b":3}"#, "[1,2]", "1", "2", "3"]);
+}
+
+#[test]
+fn postwalk_visits_children_before_parents() {
+    let (result, visits) = run_with_visits(
+        "postwalk: [{ $: visit }, { $: tree }]",
+        "{ a: [1, 2], b: 3 }",
+    );
+    assert_eq!(result, vm::parse("{ a: [1, 2], b: 3 }"));
+    assert_eq!(visits, ["1", "2", "[1,2]", "3", r#"{"a":[1,2],"b":3}"#]);
+}
+
+#[test]
+fn walk_applies_inner_to_children_and_outer_to_the_result() {
+    let (result, visits) = run_with_visits(
+        "
+walk:
+  - lambda: x
+    +in: [{ $: x }]
+  - { $: visit }
+  - { $: tree }
+",
+        "[1, [2]]",
+    );
+    assert_eq!(result, vm::parse("[[1], [[2]]]"));
+    assert_eq!(visits, ["[[1],[[2]]]"]);
+}
+
+#[test]
+fn prewalk_descends_into_replacements() {
+    let program = "
+prewalk:
+  - lambda: x
+    +in:
+      case: { $: x }
+      +of:
+        - +pattern: { +type: number }
+          +then: [marker]
+        - +pattern: marker
+          +then: seen
+      +default: { $: x }
+  - { $: tree }
+";
+    let (result, _) = run_with_visits(program, "{ a: 1 }");
+    assert_eq!(result, vm::parse("{ a: [seen] }"));
+}
+
+#[test]
+fn walkers_can_take_the_path_of_each_node() {
+    let program = "
+postwalk:
+  - lambda: [node, path]
+    +in:
+      case: { $: node }
+      +of:
+        - +pattern: { +type: number }
+          +then: { $: path }
+      +default: { $: node }
+  - { $: tree }
+  - quote: { path: true }
+";
+    let (result, _) = run_with_visits(program, "{ a: [1, { b: 2 }], c: 3 }");
+    assert_eq!(
+        result,
+        vm::parse("{ a: [[a, 0], { b: [a, 1, b] }], c: [c] }")
+    );
+    let (result, _) = run_with_visits(
+        "postwalk: [{ $: visit }, { $: tree }, { quote: { depth: 1 } }]",
+        "[]",
+    );
+    assert_eq!(
+        result,
+        Err(vm::Error::InvalidOperation(
+            "Unknown option: depth".to_string()
+        ))
+    );
+}
+
+#[test]
+fn unchanged_subtrees_are_shared() {
+    let tree = vm::parse("{ a: { b: [1, 2] }, c: [3] }").unwrap();
+    let mut engine = vm::Engine::new();
+    engine.bind_value("tree", tree.clone());
+    let context = engine.context("main.yapl").unwrap();
+    let program = "
+postwalk:
+  - lambda: x
+    +in:
+      case: { $: x }
+      +of:
+        - +pattern: 3
+          +then: three
+      +default: { $: x }
+  - { $: tree }
+";
+    let result = context.run(program).unwrap();
+    assert_eq!(
+        result,
+        vm::parse("{ a: { b: [1, 2] }, c: [three] }").unwrap()
+    );
+    let (before, after) = match (&tree, &result) {
+        (vm::Value::Object(before), vm::Value::Object(after)) => (before, after),
+        _ => unreachable!(),
+    };
+    assert!(!Arc::ptr_eq(before, after));
+    match (&before["a"], &after["a"]) {
+        (vm::Value::Object(before), vm::Value::Object(after)) => {
+            assert!(Arc::ptr_eq(before, after))
+        }
+        _ => unreachable!(),
+    }
+}